@@ -0,0 +1,58 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tauri::AppHandle;
+
+use crate::commands::{managed, run_blocking};
+use crate::commands::software::{find_software_definition, get_software_definitions};
+use crate::git::{GitExportReport, GitExporter, GitImportReport, GitImporter};
+use crate::software::SoftwareDefinition;
+use crate::storage::VersionStorage;
+
+// Software to operate on: the given ids, or every known software when none are given
+fn select_software(software_ids: &[String]) -> Result<Vec<SoftwareDefinition>, String> {
+    if software_ids.is_empty() {
+        return Ok(get_software_definitions());
+    }
+    software_ids.iter().map(|id| find_software_definition(id)).collect()
+}
+
+// Replay version history as commits in a local git repository
+#[tauri::command]
+pub async fn export_history_to_git(
+    software_ids: Vec<String>,
+    target_dir: String,
+    app_handle: AppHandle,
+) -> Result<GitExportReport, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let software = select_software(&software_ids)?;
+        let target_dir = PathBuf::from(SoftwareDefinition::expand_path(&target_dir));
+        
+        GitExporter::export_history(&storage, &software, &target_dir)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Import the history of config files in a git repository, mapping repo paths to software ids
+#[tauri::command]
+pub async fn import_git_history(
+    repo_dir: String,
+    mapping: BTreeMap<String, String>,
+    app_handle: AppHandle,
+) -> Result<GitImportReport, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        if mapping.is_empty() {
+            return Err("No repository paths selected".to_string());
+        }
+        for software_id in mapping.values() {
+            find_software_definition(software_id)?;
+        }
+        let repo_dir = PathBuf::from(SoftwareDefinition::expand_path(&repo_dir));
+        
+        GitImporter::import_history(&storage, &repo_dir, &mapping)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
@@ -1,9 +1,35 @@
+use tauri::{AppHandle, Manager, State};
+
 pub mod config;
+pub mod git;
 pub mod path;
+pub mod settings;
 pub mod software;
 pub mod version;
 
 pub use config::*;
+pub use git::*;
 pub use path::*;
+pub use settings::*;
 pub use software::*;
-pub use version::*;
\ No newline at end of file
+pub use version::*;
+
+// Run storage work on the blocking thread pool. Storage calls do file I/O and may wait on
+// another process's lock, which must not stall the async runtime that serves other commands.
+pub(crate) async fn run_blocking<T, F>(app_handle: AppHandle, work: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&AppHandle) -> Result<T, String> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(move || work(&app_handle))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// Get a state managed by the app, failing instead of panicking if its setup failed
+pub(crate) fn managed<T: Send + Sync + 'static>(app_handle: &AppHandle) -> Result<State<'_, T>, String> {
+    app_handle.try_state::<T>().ok_or_else(|| {
+        let name = std::any::type_name::<T>().rsplit("::").next().unwrap_or_default();
+        format!("{} is not available", name)
+    })
+}
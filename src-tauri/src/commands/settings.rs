@@ -0,0 +1,80 @@
+use tauri::{AppHandle, Emitter};
+
+use crate::commands::{managed, run_blocking, set_definition_dirs};
+use crate::storage::{
+    check_location_change, AppSettings, PreferencesStorage, SettingsChange, VersionStorage, SETTINGS_CHANGED_EVENT,
+};
+use crate::version::VersionManager;
+
+// Get the app-wide settings
+#[tauri::command]
+pub async fn get_app_settings(
+    app_handle: AppHandle,
+) -> Result<AppSettings, String> {
+    run_blocking(app_handle, move |app| {
+        let preferences = managed::<PreferencesStorage>(app)?;
+        preferences.get_settings()
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Validate and save the app-wide settings, applying them and notifying the frontend
+#[tauri::command]
+pub async fn save_app_settings(
+    settings: AppSettings,
+    app_handle: AppHandle,
+) -> Result<SettingsChange, String> {
+    run_blocking(app_handle, move |app| {
+        let preferences = managed::<PreferencesStorage>(app)?;
+        let storage = managed::<VersionStorage>(app)?;
+        apply_settings(settings, app, &preferences, &storage)
+    })
+    .await
+}
+
+// Restore the default settings
+#[tauri::command]
+pub async fn reset_app_settings(
+    app_handle: AppHandle,
+) -> Result<SettingsChange, String> {
+    run_blocking(app_handle, move |app| {
+        let preferences = managed::<PreferencesStorage>(app)?;
+        let storage = managed::<VersionStorage>(app)?;
+        apply_settings(AppSettings::default(), app, &preferences, &storage)
+    })
+    .await
+}
+
+// Save settings, push the ones kept elsewhere to their stores and emit the change
+pub fn apply_settings(
+    settings: AppSettings,
+    app_handle: &AppHandle,
+    preferences: &PreferencesStorage,
+    storage: &VersionStorage,
+) -> Result<SettingsChange, String> {
+    let current = preferences.get_settings()
+        .map_err(|e| e.to_string())?;
+    check_location_change(&current, &settings, storage)
+        .map_err(|e| e.to_string())?;
+    
+    let previous = preferences.save_settings(settings.clone())
+        .map_err(|e| e.to_string())?;
+    set_definition_dirs(settings.definition_dirs.clone());
+    
+    // The settings are the record; the storage's global policy and version caps follow them
+    storage.follow_retention_settings(settings.retention.clone());
+    VersionManager::sync_all_retention(storage, preferences)
+        .map_err(|e| e.to_string())?;
+    
+    let change = SettingsChange {
+        changed: settings.changed_fields(&previous).map_err(|e| e.to_string())?,
+        settings,
+    };
+    if !change.changed.is_empty() {
+        app_handle.emit(SETTINGS_CHANGED_EVENT, change.clone())
+            .map_err(|e| e.to_string())?;
+    }
+    
+    Ok(change)
+}
@@ -1,12 +1,20 @@
 use anyhow::Result;
 use serde_json::Value;
-use tauri::State;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock};
+use tauri::AppHandle;
 
+use crate::commands::{managed, run_blocking};
 use crate::software::{
     ConfigManager, SoftwareDefinition, SoftwareDetector, SoftwareStatus
 };
-use crate::storage::{VersionStorage, PreferencesStorage, SoftwarePreferences};
-use crate::version::VersionManager;
+use crate::storage::{
+    validate_software_id, VersionStorage, EffectivePreferences, PreferenceStore, PreferencesStorage,
+    SecretFinding, SoftwarePreferences
+};
+use crate::version::{BackupScheduler, VersionManager};
 
 // Get list of all supported software with their status
 #[tauri::command]
@@ -57,35 +65,53 @@ pub async fn get_software_config(
 
 // Save software configuration
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn save_software_config(
     software_id: String,
     content: String,
     parsed_content: Value,
     note: Option<String>,
-    storage: State<'_, VersionStorage>,
-) -> Result<(), String> {
-    let definitions = get_software_definitions();
-    let software = definitions
-        .into_iter()
-        .find(|d| d.id == software_id)
-        .ok_or_else(|| format!("Software {} not found", software_id))?;
-    
-    // Write configuration file
-    ConfigManager::write_config(&software, &parsed_content)
+    is_auto_save: Option<bool>,
+    app_handle: AppHandle,
+) -> Result<Vec<SecretFinding>, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let preferences = managed::<PreferencesStorage>(app)?;
+        let scheduler = managed::<BackupScheduler>(app)?;
+        let definitions = get_software_definitions();
+        let software = definitions
+            .into_iter()
+            .find(|d| d.id == software_id)
+            .ok_or_else(|| format!("Software {} not found", software_id))?;
+        
+        // Leave the file alone when auto-save is turned off for this software
+        let is_auto_save = is_auto_save.unwrap_or(false);
+        VersionManager::check_auto_save(&*preferences, &software_id, is_auto_save)
+            .map_err(|e| e.to_string())?;
+        
+        // Keep what is on disk before replacing it
+        scheduler.before_apply(&storage, &preferences, &software)
+            .map_err(|e| e.to_string())?;
+        
+        // Write configuration file
+        ConfigManager::write_config(&software, &parsed_content)
+            .map_err(|e| e.to_string())?;
+        
+        // Save version to storage, reporting any secrets it contains
+        let version = VersionManager::save_config_version(
+            &*storage,
+            &*preferences,
+            &software_id,
+            &content,
+            Some(parsed_content),
+            note,
+            is_auto_save,
+        )
         .map_err(|e| e.to_string())?;
-    
-    // Save version to storage
-    VersionManager::save_version(
-        &storage,
-        &software_id,
-        &content,
-        Some(parsed_content),
-        note,
-        false, // Manual save, not auto-save
-    )
-    .map_err(|e| e.to_string())?;
-    
-    Ok(())
+        
+        Ok(version.secrets)
+    })
+    .await
 }
 
 // Check if software is installed
@@ -138,25 +164,126 @@ pub async fn apply_template(
 #[tauri::command]
 pub async fn get_preferences(
     software_id: String,
-    storage: State<'_, PreferencesStorage>,
+    app_handle: AppHandle,
 ) -> Result<SoftwarePreferences, String> {
-    storage.get_preferences(&software_id)
-        .map_err(|e| e.to_string())
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<PreferencesStorage>(app)?;
+        storage.get_preferences(&software_id)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 // Save software preferences
 #[tauri::command]
 pub async fn save_preferences(
     preferences: SoftwarePreferences,
-    storage: State<'_, PreferencesStorage>,
+    app_handle: AppHandle,
 ) -> Result<(), String> {
-    storage.save_preferences(preferences)
-        .map_err(|e| e.to_string())
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<PreferencesStorage>(app)?;
+        let versions = managed::<VersionStorage>(app)?;
+        VersionManager::save_preferences(&*versions, &*storage, preferences)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Get software preferences and whether they are inherited from the app settings
+#[tauri::command]
+pub async fn get_effective_preferences(
+    software_id: String,
+    app_handle: AppHandle,
+) -> Result<EffectivePreferences, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<PreferencesStorage>(app)?;
+        storage.resolve_preferences(&software_id)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Drop a software's own preferences so it follows the app settings again
+#[tauri::command]
+pub async fn reset_preferences(
+    software_id: String,
+    app_handle: AppHandle,
+) -> Result<EffectivePreferences, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<PreferencesStorage>(app)?;
+        let versions = managed::<VersionStorage>(app)?;
+        storage.reset_preferences(&software_id)
+            .map_err(|e| e.to_string())?;
+        
+        let effective = storage.resolve_preferences(&software_id)
+            .map_err(|e| e.to_string())?;
+        VersionManager::sync_retention(&*versions, &effective.preferences)
+            .map_err(|e| e.to_string())?;
+        Ok(effective)
+    })
+    .await
+}
+
+// Look up a registered software definition, rejecting unknown ids
+pub fn find_software_definition(software_id: &str) -> Result<SoftwareDefinition, String> {
+    get_software_definitions()
+        .into_iter()
+        .find(|d| d.id == software_id)
+        .ok_or_else(|| format!("Software {} not found", software_id))
+}
+
+// Directories from the app settings searched for additional software definitions
+static DEFINITION_DIRS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+// Follow the `definition_dirs` app setting
+pub fn set_definition_dirs(dirs: Vec<String>) {
+    *DEFINITION_DIRS.write().unwrap_or_else(PoisonError::into_inner) = dirs;
 }
 
-// Helper function to get software definitions
-// In a real app, this would load from a file or embedded resource
+// Built-in definitions, followed by those found in the configured definition directories
 pub fn get_software_definitions() -> Vec<SoftwareDefinition> {
+    let dirs = DEFINITION_DIRS.read().unwrap_or_else(PoisonError::into_inner).clone();
+    let mut definitions = builtin_definitions();
+    for dir in dirs {
+        add_definitions_from(&mut definitions, Path::new(&SoftwareDefinition::expand_path(&dir)));
+    }
+    definitions
+}
+
+// Add each `*.json` definition in `dir`, in file name order. Ids that are already defined are
+// skipped, so a file cannot replace a built-in definition.
+fn add_definitions_from(definitions: &mut Vec<SoftwareDefinition>, dir: &Path) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+        Err(e) => {
+            log::warn!("Failed to read definition directory {:?}: {}", dir, e);
+            return;
+        }
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    
+    for path in paths {
+        let definition = fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| Ok(serde_json::from_str::<SoftwareDefinition>(&content)?))
+            .and_then(|definition| validate_software_id(&definition.id).map(|_| definition));
+        match definition {
+            Ok(definition) if definitions.iter().any(|d| d.id == definition.id) => {
+                log::warn!("Skipping {:?}: software {} is already defined", path, definition.id);
+            }
+            Ok(definition) => definitions.push(definition),
+            Err(e) => log::warn!("Skipping software definition {:?}: {}", path, e),
+        }
+    }
+}
+
+// Definitions shipped with the app
+fn builtin_definitions() -> Vec<SoftwareDefinition> {
     use crate::software::{
         ConfigSchema, ConfigSection, ConfigField, FieldType,
         SoftwareCategory, ConfigFormat
@@ -210,4 +337,29 @@ pub fn get_software_definitions() -> Vec<SoftwareDefinition> {
         },
         // Add more software definitions here...
     ]
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_definitions_are_added_from_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut fish = builtin_definitions().remove(0);
+        fish.id = "fish".to_string();
+        fs::write(dir.path().join("fish.json"), serde_json::to_string(&fish).unwrap()).unwrap();
+        // Built-in ids, invalid ids, unparseable files and other extensions are skipped
+        fs::write(dir.path().join("zsh.json"), serde_json::to_string(&builtin_definitions()[0]).unwrap()).unwrap();
+        fish.id = "../escape".to_string();
+        fs::write(dir.path().join("escape.json"), serde_json::to_string(&fish).unwrap()).unwrap();
+        fs::write(dir.path().join("broken.json"), "{").unwrap();
+        fs::write(dir.path().join("notes.txt"), "fish").unwrap();
+        
+        let mut definitions = builtin_definitions();
+        add_definitions_from(&mut definitions, dir.path());
+        add_definitions_from(&mut definitions, &dir.path().join("missing"));
+        let ids: Vec<&str> = definitions.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, vec!["zsh", "fish"]);
+    }
+}
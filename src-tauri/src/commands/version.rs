@@ -1,18 +1,200 @@
-use tauri::State;
+use tauri::AppHandle;
 
+use crate::commands::{managed, run_blocking};
+use crate::commands::settings::apply_settings;
+use crate::commands::software::find_software_definition;
 use crate::software::ConfigVersion;
-use crate::storage::VersionStorage;
-use crate::version::VersionManager;
+use crate::storage::{
+    scan_secrets, validate_version_id, CompactionReport, EncryptionStatus, HistoryPage,
+    AppSettings, BackupSchedule, HistoryQuery, MigrationReport, PreferenceStore, PreferencesStorage, PrunePlan,
+    RetentionPolicy, SecretFinding, SecretHandling, SecretPolicy, SqliteStore, SquashReport,
+    StorageBackend, StorageMigrator, StorageStats, StorageVerification, VersionStore,
+    VersionStorage,
+};
+use crate::version::{
+    BackupRunReport, BackupScheduler, BackupSchedulerStatus, BackupTrigger, BlameEngine,
+    BlameReport, ConflictResolution, DiffEngine, DriftDetector, DriftReport,
+    MergeEngine, MergeResult, PartialRestore, PartialRestoreResult, RestoreSelection, SearchIndex,
+    SearchQuery, SearchResults, VersionDiff, VersionManager, CURRENT_VERSION_ID,
+};
+
+// Reject version ids that are not well-formed before they reach storage
+fn check_version_id(version_id: &str) -> Result<(), String> {
+    validate_version_id(version_id).map_err(|e| e.to_string())
+}
+
+// Like check_version_id, but also accepts the "current" pseudo version
+fn check_snapshot_id(version_id: &str) -> Result<(), String> {
+    if version_id == CURRENT_VERSION_ID {
+        return Ok(());
+    }
+    check_version_id(version_id)
+}
 
 // Get version history for a software
 #[tauri::command]
 pub async fn get_version_history(
     software_id: String,
     limit: Option<usize>,
-    storage: State<'_, VersionStorage>,
+    tag: Option<String>,
+    app_handle: AppHandle,
 ) -> Result<Vec<ConfigVersion>, String> {
-    VersionManager::get_history(&storage, &software_id, limit)
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        match tag {
+            Some(tag) => VersionManager::get_tagged_versions(&*storage, &software_id, &tag, limit),
+            None => VersionManager::get_history(&*storage, &software_id, limit),
+        }
         .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// List version metadata page by page; load content with get_version when needed
+#[tauri::command]
+pub async fn query_version_history(
+    software_id: String,
+    query: Option<HistoryQuery>,
+    app_handle: AppHandle,
+) -> Result<HistoryPage, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.query_history(&software_id, &query.unwrap_or_default())
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Get the newest version carrying a tag, e.g. "known-good"
+#[tauri::command]
+pub async fn get_tagged_version(
+    software_id: String,
+    tag: String,
+    app_handle: AppHandle,
+) -> Result<Option<ConfigVersion>, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        VersionManager::get_tagged_version(&*storage, &software_id, &tag)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// List every tag used in a software's history
+#[tauri::command]
+pub async fn list_version_tags(
+    software_id: String,
+    app_handle: AppHandle,
+) -> Result<Vec<String>, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.list_tags(&software_id)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Tag a version
+#[tauri::command]
+pub async fn add_version_tag(
+    software_id: String,
+    version_id: String,
+    tag: String,
+    app_handle: AppHandle,
+) -> Result<ConfigVersion, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        check_version_id(&version_id)?;
+        
+        storage.add_tag(&software_id, &version_id, &tag)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Remove a tag from a version
+#[tauri::command]
+pub async fn remove_version_tag(
+    software_id: String,
+    version_id: String,
+    tag: String,
+    app_handle: AppHandle,
+) -> Result<ConfigVersion, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        check_version_id(&version_id)?;
+        
+        storage.remove_tag(&software_id, &version_id, &tag)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Label a version with free-form text
+#[tauri::command]
+pub async fn add_version_label(
+    software_id: String,
+    version_id: String,
+    label: String,
+    app_handle: AppHandle,
+) -> Result<ConfigVersion, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        check_version_id(&version_id)?;
+        
+        storage.add_label(&software_id, &version_id, &label)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Remove a label from a version
+#[tauri::command]
+pub async fn remove_version_label(
+    software_id: String,
+    version_id: String,
+    label: String,
+    app_handle: AppHandle,
+) -> Result<ConfigVersion, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        check_version_id(&version_id)?;
+        
+        storage.remove_label(&software_id, &version_id, &label)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Pin or unpin a version; pinned versions survive cleanup
+#[tauri::command]
+pub async fn set_version_pinned(
+    software_id: String,
+    version_id: String,
+    pinned: bool,
+    app_handle: AppHandle,
+) -> Result<ConfigVersion, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        check_version_id(&version_id)?;
+        
+        storage.set_pinned(&software_id, &version_id, pinned)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 // Get a specific version
@@ -20,10 +202,17 @@ pub async fn get_version_history(
 pub async fn get_version(
     software_id: String,
     version_id: String,
-    storage: State<'_, VersionStorage>,
+    app_handle: AppHandle,
 ) -> Result<ConfigVersion, String> {
-    VersionManager::get_version(&storage, &software_id, &version_id)
-        .map_err(|e| e.to_string())
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        check_version_id(&version_id)?;
+        
+        VersionManager::get_version(&*storage, &software_id, &version_id)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 // Restore a specific version
@@ -31,48 +220,135 @@ pub async fn get_version(
 pub async fn restore_version(
     software_id: String,
     version_id: String,
-    storage: State<'_, VersionStorage>,
+    app_handle: AppHandle,
 ) -> Result<(), String> {
-    // Get the version
-    let version = VersionManager::get_version(&storage, &software_id, &version_id)
-        .map_err(|e| e.to_string())?;
-    
-    // Get software definition
-    let definitions = crate::commands::software::get_software_definitions();
-    let software = definitions
-        .into_iter()
-        .find(|d| d.id == version.software_id)
-        .ok_or_else(|| format!("Software {} not found", version.software_id))?;
-    
-    // Write the configuration
-    if let Some(ref parsed) = version.parsed_content {
-        crate::software::ConfigManager::write_config(&software, parsed)
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let preferences = managed::<PreferencesStorage>(app)?;
+        let scheduler = managed::<BackupScheduler>(app)?;
+        // Get software definition
+        let software = find_software_definition(&software_id)?;
+        check_version_id(&version_id)?;
+        
+        // Get the version
+        let version = VersionManager::get_restorable_version(&storage, &software_id, &version_id)
             .map_err(|e| e.to_string())?;
-    } else {
-        // If no parsed content, write raw content
-        // This is a fallback for plain text configs
-        let paths = software.get_config_path()
-            .ok_or_else(|| "No config path for current platform".to_string())?;
-        
-        if !paths.is_empty() {
-            let path = crate::software::SoftwareDefinition::expand_path(&paths[0]);
-            std::fs::write(&path, &version.content)
-                .map_err(|e| format!("Failed to write config: {}", e))?;
+        
+        // Placeholders would replace the real secrets; a merge keeps the current values instead
+        if version.secrets.iter().any(|s| s.handling == SecretHandling::Redacted) {
+            return Err(format!(
+                "Version {} has redacted secrets; restore it with a merge to keep the current values",
+                version_id
+            ));
         }
-    }
-    
-    // Save a new version marking this as a restore
-    VersionManager::save_version(
-        &storage,
-        &version.software_id,
-        &version.content,
-        version.parsed_content,
-        Some(format!("Restored from version {}", version_id)),
-        false,
-    )
-    .map_err(|e| e.to_string())?;
-    
-    Ok(())
+        
+        scheduler.before_apply(&storage, &preferences, &software)
+            .map_err(|e| e.to_string())?;
+        
+        // Write the configuration
+        if let Some(ref parsed) = version.parsed_content {
+            crate::software::ConfigManager::write_config(&software, parsed)
+                .map_err(|e| e.to_string())?;
+        } else {
+            // If no parsed content, write raw content
+            // This is a fallback for plain text configs
+            let paths = software.get_config_path()
+                .ok_or_else(|| "No config path for current platform".to_string())?;
+            
+            if !paths.is_empty() {
+                let path = crate::software::SoftwareDefinition::expand_path(&paths[0]);
+                std::fs::write(&path, &version.content)
+                    .map_err(|e| format!("Failed to write config: {}", e))?;
+            }
+        }
+        
+        // Save a new version marking this as a restore
+        VersionManager::save_version(
+            &*storage,
+            &version.software_id,
+            &version.content,
+            version.parsed_content,
+            Some(format!("Restored from version {}", version_id)),
+            false,
+        )
+        .map_err(|e| e.to_string())?;
+        
+        Ok(())
+    })
+    .await
+}
+
+// Merge a historical version into the current file instead of overwriting it.
+// With the default resolution nothing is written while conflicts remain.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn restore_version_merge(
+    software_id: String,
+    version_id: String,
+    base_version_id: Option<String>,
+    resolution: Option<ConflictResolution>,
+    dry_run: Option<bool>,
+    app_handle: AppHandle,
+) -> Result<MergeResult, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let preferences = managed::<PreferencesStorage>(app)?;
+        let scheduler = managed::<BackupScheduler>(app)?;
+        let software = find_software_definition(&software_id)?;
+        check_version_id(&version_id)?;
+        if let Some(ref base) = base_version_id {
+            check_version_id(base)?;
+        }
+        let dry_run = dry_run.unwrap_or(false);
+        if !dry_run {
+            scheduler.before_apply(&storage, &preferences, &software)
+                .map_err(|e| e.to_string())?;
+        }
+        
+        MergeEngine::restore_merge(
+            &storage,
+            &software,
+            &version_id,
+            base_version_id.as_deref(),
+            resolution.unwrap_or_default(),
+            dry_run,
+        )
+        .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Restore only the selected key paths, line ranges or shell elements of a version
+#[tauri::command]
+pub async fn restore_partial(
+    software_id: String,
+    version_id: String,
+    selection: RestoreSelection,
+    dry_run: Option<bool>,
+    app_handle: AppHandle,
+) -> Result<PartialRestoreResult, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let preferences = managed::<PreferencesStorage>(app)?;
+        let scheduler = managed::<BackupScheduler>(app)?;
+        let software = find_software_definition(&software_id)?;
+        check_version_id(&version_id)?;
+        let dry_run = dry_run.unwrap_or(false);
+        if !dry_run {
+            scheduler.before_apply(&storage, &preferences, &software)
+                .map_err(|e| e.to_string())?;
+        }
+        
+        PartialRestore::restore_partial(
+            &storage,
+            &software,
+            &version_id,
+            &selection,
+            dry_run,
+        )
+        .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 // Delete a version
@@ -80,10 +356,17 @@ pub async fn restore_version(
 pub async fn delete_version(
     software_id: String,
     version_id: String,
-    storage: State<'_, VersionStorage>,
+    app_handle: AppHandle,
 ) -> Result<(), String> {
-    VersionManager::delete_version(&storage, &software_id, &version_id)
-        .map_err(|e| e.to_string())
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        check_version_id(&version_id)?;
+        
+        VersionManager::delete_version(&*storage, &software_id, &version_id)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 // Set maximum versions to keep
@@ -91,20 +374,315 @@ pub async fn delete_version(
 pub async fn set_max_versions(
     software_id: String,
     max_versions: usize,
-    storage: State<'_, VersionStorage>,
+    app_handle: AppHandle,
 ) -> Result<(), String> {
-    VersionManager::set_max_versions(&storage, &software_id, max_versions)
-        .map_err(|e| e.to_string())
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let preferences = managed::<PreferencesStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        VersionManager::set_max_versions(&*storage, &*preferences, &software_id, max_versions)
+            .map_err(|e| e.to_string())
+    })
+    .await
 }
 
 // Get maximum versions setting
 #[tauri::command]
 pub async fn get_max_versions(
     software_id: String,
-    storage: State<'_, VersionStorage>,
+    app_handle: AppHandle,
 ) -> Result<usize, String> {
-    VersionManager::get_max_versions(&storage, &software_id)
+    run_blocking(app_handle, move |app| {
+        let preferences = managed::<PreferencesStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        VersionManager::get_max_versions(&*preferences, &software_id)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Get the retention policy in force for a software, or the global policy when no software is given
+#[tauri::command]
+pub async fn get_retention_policy(
+    software_id: Option<String>,
+    app_handle: AppHandle,
+) -> Result<RetentionPolicy, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let preferences = managed::<PreferencesStorage>(app)?;
+        match software_id {
+            Some(software_id) => {
+                find_software_definition(&software_id)?;
+                storage.get_retention_policy(&software_id)
+            }
+            None => preferences.get_settings().map(|settings| settings.retention),
+        }
         .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Set a software's retention override, or the global policy when no software is given.
+// A `None` policy clears the override or restores the global defaults.
+#[tauri::command]
+pub async fn set_retention_policy(
+    software_id: Option<String>,
+    policy: Option<RetentionPolicy>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let preferences = managed::<PreferencesStorage>(app)?;
+        match software_id {
+            Some(software_id) => {
+                find_software_definition(&software_id)?;
+                storage.set_retention_policy(&software_id, policy)
+                    .map_err(|e| e.to_string())
+            }
+            // The global policy is part of the app settings
+            None => {
+                let settings = preferences.get_settings().map_err(|e| e.to_string())?;
+                let settings = AppSettings {
+                    retention: policy.unwrap_or_default(),
+                    ..settings
+                };
+                apply_settings(settings, app, &preferences, &storage).map(|_| ())
+            }
+        }
+    })
+    .await
+}
+
+// List the versions the retention policy would remove, without removing them
+#[tauri::command]
+pub async fn prune_preview(
+    software_id: String,
+    app_handle: AppHandle,
+) -> Result<PrunePlan, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.prune_preview(&software_id)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Remove the versions the retention policy no longer keeps
+#[tauri::command]
+pub async fn prune_versions(
+    software_id: String,
+    app_handle: AppHandle,
+) -> Result<PrunePlan, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.prune(&software_id)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Coalesce runs of consecutive auto-saves into their newest version
+#[tauri::command]
+pub async fn squash_auto_saves(
+    software_id: String,
+    window_minutes: Option<u32>,
+    app_handle: AppHandle,
+) -> Result<SquashReport, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.squash_auto_saves(&software_id, window_minutes)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Restore the auto-saves hidden by a squash, returning their ids
+#[tauri::command]
+pub async fn unsquash_version(
+    software_id: String,
+    version_id: String,
+    app_handle: AppHandle,
+) -> Result<Vec<String>, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        check_version_id(&version_id)?;
+        
+        storage.unsquash_version(&software_id, &version_id)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_encryption_status(
+    software_id: String,
+    app_handle: AppHandle,
+) -> Result<EncryptionStatus, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.encryption_status(&software_id)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Encrypt the stored history of a software with a passphrase
+#[tauri::command]
+pub async fn enable_history_encryption(
+    software_id: String,
+    passphrase: String,
+    app_handle: AppHandle,
+) -> Result<EncryptionStatus, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.enable_encryption(&software_id, &passphrase)
+            .and_then(|_| storage.encryption_status(&software_id))
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Make encrypted history readable for the rest of the session
+#[tauri::command]
+pub async fn unlock_history(
+    software_id: String,
+    passphrase: String,
+    app_handle: AppHandle,
+) -> Result<EncryptionStatus, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.unlock_history(&software_id, &passphrase)
+            .and_then(|_| storage.encryption_status(&software_id))
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn lock_history(
+    software_id: String,
+    app_handle: AppHandle,
+) -> Result<EncryptionStatus, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.lock_history(&software_id)
+            .and_then(|_| storage.encryption_status(&software_id))
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Change the passphrase and re-encrypt the history with a new key
+#[tauri::command]
+pub async fn rotate_history_key(
+    software_id: String,
+    passphrase: String,
+    new_passphrase: String,
+    app_handle: AppHandle,
+) -> Result<EncryptionStatus, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.rotate_encryption_key(&software_id, &passphrase, &new_passphrase)
+            .and_then(|_| storage.encryption_status(&software_id))
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn disable_history_encryption(
+    software_id: String,
+    passphrase: String,
+    app_handle: AppHandle,
+) -> Result<EncryptionStatus, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.disable_encryption(&software_id, &passphrase)
+            .and_then(|_| storage.encryption_status(&software_id))
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_secret_policy(
+    software_id: String,
+    app_handle: AppHandle,
+) -> Result<SecretPolicy, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.get_secret_policy(&software_id)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Choose how secrets are stored in versions saved from now on
+#[tauri::command]
+pub async fn set_secret_policy(
+    software_id: String,
+    policy: SecretPolicy,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.set_secret_policy(&software_id, policy)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Set the passphrase that protects secrets set aside by the encrypt policy
+#[tauri::command]
+pub async fn enable_secret_encryption(
+    software_id: String,
+    passphrase: String,
+    app_handle: AppHandle,
+) -> Result<EncryptionStatus, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.enable_secret_encryption(&software_id, &passphrase)
+            .and_then(|_| storage.encryption_status(&software_id))
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Report likely secrets in the current config file
+#[tauri::command]
+pub async fn scan_config_secrets(software_id: String) -> Result<Vec<SecretFinding>, String> {
+    let software = find_software_definition(&software_id)?;
+    
+    let (content, _) = crate::software::ConfigManager::read_config(&software)
+        .map_err(|e| e.to_string())?;
+    Ok(scan_secrets(&content))
 }
 
 // Create a backup
@@ -112,27 +690,221 @@ pub async fn get_max_versions(
 pub async fn create_backup(
     software_id: String,
     note: Option<String>,
-    storage: State<'_, VersionStorage>,
+    app_handle: AppHandle,
 ) -> Result<ConfigVersion, String> {
-    let definitions = crate::commands::software::get_software_definitions();
-    let software = definitions
-        .into_iter()
-        .find(|d| d.id == software_id)
-        .ok_or_else(|| format!("Software {} not found", software_id))?;
-    
-    // Read current configuration
-    let (content, parsed) = crate::software::ConfigManager::read_config(&software)
-        .map_err(|e| e.to_string())?;
-    
-    // Save as backup
-    VersionManager::save_version(
-        &storage,
-        &software_id,
-        &content,
-        Some(parsed),
-        note.or_else(|| Some("Manual backup".to_string())),
-        false,
-    )
-    .map_err(|e| e.to_string())
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let software = find_software_definition(&software_id)?;
+        
+        // Read current configuration
+        let (content, parsed) = crate::software::ConfigManager::read_config(&software)
+            .map_err(|e| e.to_string())?;
+        
+        // Save as backup
+        VersionManager::save_version(
+            &*storage,
+            &software_id,
+            &content,
+            Some(parsed),
+            note.or_else(|| Some("Manual backup".to_string())),
+            false,
+        )
+        .map_err(|e| e.to_string())
+    })
+    .await
 }
 
+// Automatic backup schedule, next due run and the outcome of the last run
+#[tauri::command]
+pub async fn get_backup_status(
+    app_handle: AppHandle,
+) -> Result<BackupSchedulerStatus, String> {
+    run_blocking(app_handle, move |app| {
+        let preferences = managed::<PreferencesStorage>(app)?;
+        let scheduler = managed::<BackupScheduler>(app)?;
+        scheduler.status(&preferences)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Change when automatic backups are taken
+#[tauri::command]
+pub async fn set_backup_schedule(
+    schedule: BackupSchedule,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    run_blocking(app_handle, move |app| {
+        let preferences = managed::<PreferencesStorage>(app)?;
+        // The cadence is tracked by the backend, not chosen by the caller
+        let last_run = preferences.get_backup_schedule().map_err(|e| e.to_string())?.last_run;
+        preferences.save_backup_schedule(BackupSchedule { last_run, ..schedule })
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Back up every installed software now, skipping unchanged and opted-out configs
+#[tauri::command]
+pub async fn run_backups_now(app_handle: AppHandle) -> Result<BackupRunReport, String> {
+    run_blocking(app_handle, |app| {
+        BackupScheduler::run_for_app(app, BackupTrigger::Manual)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Compare every managed config file with its last app-saved version
+#[tauri::command]
+pub async fn get_drift_report(
+    app_handle: AppHandle,
+) -> Result<DriftReport, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let definitions = crate::commands::software::get_software_definitions();
+        
+        DriftDetector::report(&storage, &definitions)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Check version storage for orphans, missing blobs and checksum mismatches
+#[tauri::command]
+pub async fn verify_storage(
+    app_handle: AppHandle,
+) -> Result<StorageVerification, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        storage.verify_storage()
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Disk usage of version history per software
+#[tauri::command]
+pub async fn get_storage_stats(
+    app_handle: AppHandle,
+) -> Result<StorageStats, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        storage.get_storage_stats()
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Rebuild indexes, remove orphaned and partial files and recompress blobs
+#[tauri::command]
+pub async fn compact_storage(
+    app_handle: AppHandle,
+) -> Result<CompactionReport, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        storage.compact_storage()
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Copy history and preferences between the file store and the SQLite database. The source is
+// left in place; the app keeps reading the file store.
+#[tauri::command]
+pub async fn migrate_storage(
+    to: StorageBackend,
+    app_handle: AppHandle,
+) -> Result<MigrationReport, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let preferences = managed::<PreferencesStorage>(app)?;
+        let database = SqliteStore::new(app).map_err(|e| e.to_string())?;
+        let settings = preferences.get_settings().map_err(|e| e.to_string())?;
+        database.follow_retention_settings(settings.retention);
+        let files: (StorageBackend, &dyn VersionStore, &dyn PreferenceStore) =
+            (StorageBackend::Files, &*storage, &*preferences);
+        let sqlite: (StorageBackend, &dyn VersionStore, &dyn PreferenceStore) =
+            (StorageBackend::Sqlite, &database, &database);
+        
+        let (from, to) = match to {
+            StorageBackend::Sqlite => (files, sqlite),
+            StorageBackend::Files => (sqlite, files),
+        };
+        StorageMigrator::migrate(from, to)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Reconstruct a software's version index from its version records
+#[tauri::command]
+pub async fn rebuild_version_index(
+    software_id: String,
+    app_handle: AppHandle,
+) -> Result<usize, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        find_software_definition(&software_id)?;
+        
+        storage.rebuild_index(&software_id)
+            .map(|index| index.versions.len())
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Attribute each line and key of the current config file to the version that introduced it
+#[tauri::command]
+pub async fn blame(
+    software_id: String,
+    app_handle: AppHandle,
+) -> Result<BlameReport, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let software = find_software_definition(&software_id)?;
+        
+        BlameEngine::blame(&storage, &software)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Search current config files and every stored version
+#[tauri::command]
+pub async fn search_configs(
+    query: SearchQuery,
+    app_handle: AppHandle,
+) -> Result<SearchResults, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let index = managed::<SearchIndex>(app)?;
+        for software_id in &query.software_ids {
+            find_software_definition(software_id)?;
+        }
+        let definitions = crate::commands::software::get_software_definitions();
+        
+        index.search(&storage, &definitions, &query)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+// Diff two versions of a software; either side may be "current" for the file on disk
+#[tauri::command]
+pub async fn diff_versions(
+    software_id: String,
+    from: String,
+    to: String,
+    app_handle: AppHandle,
+) -> Result<VersionDiff, String> {
+    run_blocking(app_handle, move |app| {
+        let storage = managed::<VersionStorage>(app)?;
+        let software = find_software_definition(&software_id)?;
+        check_snapshot_id(&from)?;
+        check_snapshot_id(&to)?;
+        
+        DiffEngine::diff_versions(&storage, &software, &from, &to)
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
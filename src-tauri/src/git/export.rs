@@ -0,0 +1,212 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::software::{ConfigVersion, SoftwareDefinition};
+use crate::storage::VersionStorage;
+
+use super::GitRepo;
+
+// Commit trailers linking git history back to stored versions
+pub const SOFTWARE_TRAILER: &str = "Config-Software";
+pub const VERSION_TRAILER: &str = "Config-Version";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SoftwareExport {
+    pub software_id: String,
+    // Path of the config file inside the repository
+    pub path: String,
+    pub exported: usize,
+    // Versions already present from an earlier export
+    pub skipped: usize,
+    // Versions older than the file's last commit, which would land on top of newer history.
+    // They are left out; exporting into a fresh repository includes them.
+    pub out_of_order: Vec<String>,
+    // Encrypted history is left out so its content never reaches the repository in the clear
+    pub encrypted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitExportReport {
+    pub target_dir: String,
+    pub commits_created: usize,
+    pub software: Vec<SoftwareExport>,
+    pub head: Option<String>,
+}
+
+pub struct GitExporter;
+
+impl GitExporter {
+    // Replay stored versions as commits, oldest first across all software. Versions
+    // already exported (found via their commit trailer) are skipped, so re-running is incremental.
+    // Versions added since with an older timestamp, e.g. by an import, are reported instead.
+    pub fn export_history(
+        storage: &VersionStorage,
+        software: &[SoftwareDefinition],
+        target_dir: &Path,
+    ) -> Result<GitExportReport> {
+        let repo = GitRepo::open_or_init(target_dir)?;
+        repo.ensure_nothing_staged()?;
+        let exported = repo.trailer_values(VERSION_TRAILER)?;
+
+        let mut reports = Vec::new();
+        let mut pending: Vec<(usize, ConfigVersion)> = Vec::new();
+
+        for definition in software {
+            let mut report = SoftwareExport {
+                software_id: definition.id.clone(),
+                path: Self::repo_path(definition),
+                exported: 0,
+                skipped: 0,
+                out_of_order: Vec::new(),
+                encrypted: storage.encryption_status(&definition.id)?.encrypted,
+            };
+            if report.encrypted {
+                reports.push(report);
+                continue;
+            }
+
+            let last_commit = repo.last_commit_time(&report.path)?;
+            for version in storage.get_history(&definition.id, None)? {
+                if exported.contains(&version.id) {
+                    report.skipped += 1;
+                } else if last_commit.is_some_and(|last| version.timestamp < last) {
+                    report.out_of_order.push(version.id);
+                } else {
+                    report.exported += 1;
+                    pending.push((reports.len(), version));
+                }
+            }
+            reports.push(report);
+        }
+
+        pending.sort_by_key(|(_, version)| version.timestamp);
+
+        for (software_index, version) in &pending {
+            let path = &reports[*software_index].path;
+            let file = repo.root().join(path);
+            if let Some(parent) = file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&file, &version.content)
+                .with_context(|| format!("Failed to write {:?}", file))?;
+
+            repo.run(&["add", "--", path])?;
+            repo.commit(&Self::commit_message(version), version.timestamp)?;
+        }
+
+        if !pending.is_empty() {
+            log::info!("Exported {} versions to {:?}", pending.len(), target_dir);
+        }
+
+        Ok(GitExportReport {
+            target_dir: target_dir.to_string_lossy().to_string(),
+            commits_created: pending.len(),
+            software: reports,
+            head: repo.head(),
+        })
+    }
+
+    // `<software id>/<config file name>`, e.g. `zsh/.zshrc`
+    pub fn repo_path(software: &SoftwareDefinition) -> String {
+        let file_name = software
+            .get_config_path()
+            .and_then(|paths| paths.first().cloned())
+            .and_then(|path| {
+                let expanded = SoftwareDefinition::expand_path(&path);
+                Path::new(&expanded).file_name().map(|n| n.to_string_lossy().to_string())
+            })
+            .unwrap_or_else(|| "config".to_string());
+
+        format!("{}/{}", software.id, file_name)
+    }
+
+    fn commit_message(version: &ConfigVersion) -> String {
+        let summary = match (&version.note, version.is_auto_save) {
+            (Some(note), _) if !note.trim().is_empty() => note.trim().to_string(),
+            (_, true) => "Auto-save".to_string(),
+            (_, false) => "Manual save".to_string(),
+        };
+
+        format!(
+            "{}: {}\n\n{}: {}\n{}: {}\n",
+            version.software_id,
+            summary,
+            SOFTWARE_TRAILER,
+            version.software_id,
+            VERSION_TRAILER,
+            version.id,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_is_incremental_and_keeps_timestamps() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = VersionStorage::with_base_path(dir.path().join("versions")).unwrap();
+        let software = vec![crate::commands::software::find_software_definition("zsh").unwrap()];
+        let target = dir.path().join("dotfiles");
+
+        let first = storage.save_version("zsh", "export A=1\n", Some("Initial".into()), false).unwrap();
+        let report = GitExporter::export_history(&storage, &software, &target).unwrap();
+        assert_eq!(report.commits_created, 1);
+
+        storage.save_version("zsh", "export A=2\n", None, true).unwrap();
+        let report = GitExporter::export_history(&storage, &software, &target).unwrap();
+        assert_eq!(report.commits_created, 1);
+        assert_eq!(report.software[0].skipped, 1);
+
+        let repo = GitRepo::open_or_init(&target).unwrap();
+        let log = repo.run(&["log", "--reverse", "--format=%at %s"]).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines[0], format!("{} zsh: Initial", first.timestamp.timestamp()));
+        assert!(lines[1].ends_with("zsh: Auto-save"));
+
+        let path = GitExporter::repo_path(&software[0]);
+        assert_eq!(fs::read_to_string(target.join(path)).unwrap(), "export A=2\n");
+    }
+
+    #[test]
+    fn test_older_versions_are_not_committed_over_newer_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = VersionStorage::with_base_path(dir.path().join("versions")).unwrap();
+        let software = vec![crate::commands::software::find_software_definition("zsh").unwrap()];
+        let target = dir.path().join("dotfiles");
+
+        storage.save_version("zsh", "export A=2\n", None, false).unwrap();
+        GitExporter::export_history(&storage, &software, &target).unwrap();
+
+        let older = chrono::DateTime::from_timestamp(1_600_000_000, 0).unwrap();
+        let imported = storage.import_version("zsh", "export A=1\n", None, older, "abc123").unwrap().unwrap();
+        storage.save_version("zsh", "export A=3\n", None, false).unwrap();
+
+        let report = GitExporter::export_history(&storage, &software, &target).unwrap();
+        assert_eq!(report.commits_created, 1);
+        assert_eq!(report.software[0].out_of_order, vec![imported.id]);
+
+        let path = GitExporter::repo_path(&software[0]);
+        assert_eq!(fs::read_to_string(target.join(path)).unwrap(), "export A=3\n");
+    }
+
+    #[test]
+    fn test_encrypted_history_is_not_exported() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = VersionStorage::with_base_path(dir.path().join("versions")).unwrap();
+        let software = vec![crate::commands::software::find_software_definition("zsh").unwrap()];
+        let target = dir.path().join("dotfiles");
+
+        storage.save_version("zsh", "export TOKEN=hunter2\n", None, false).unwrap();
+        storage.enable_encryption("zsh", "correct horse").unwrap();
+        storage.lock_history("zsh").unwrap();
+
+        let report = GitExporter::export_history(&storage, &software, &target).unwrap();
+        assert_eq!(report.commits_created, 0);
+        assert!(report.software[0].encrypted);
+        assert!(!target.join(GitExporter::repo_path(&software[0])).exists());
+    }
+}
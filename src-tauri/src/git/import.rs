@@ -0,0 +1,181 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::storage::VersionStorage;
+
+use super::{GitRepo, VERSION_TRAILER};
+
+// Separators for `git log` output; commit messages may contain anything else
+const FIELD_SEPARATOR: char = '\u{1f}';
+const RECORD_SEPARATOR: char = '\u{1e}';
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SoftwareImport {
+    pub software_id: String,
+    // Path of the config file inside the repository
+    pub path: String,
+    pub imported: usize,
+    // Commits imported earlier, exported from this app, or deleting the file
+    pub skipped: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitImportReport {
+    pub repo_dir: String,
+    pub versions_imported: usize,
+    pub software: Vec<SoftwareImport>,
+}
+
+struct CommitInfo {
+    hash: String,
+    timestamp: DateTime<Utc>,
+    message: String,
+}
+
+pub struct GitImporter;
+
+impl GitImporter {
+    // Create a version for every commit touching each mapped path, keeping the author time and
+    // message. Each software remembers the commits it imported, so importing again only adds new
+    // commits, even after pruning.
+    pub fn import_history(
+        storage: &VersionStorage,
+        repo_dir: &Path,
+        mapping: &BTreeMap<String, String>,
+    ) -> Result<GitImportReport> {
+        let repo = GitRepo::open(repo_dir)?;
+        if !repo.has_commits() {
+            return Err(anyhow!("{:?} has no commits", repo_dir));
+        }
+
+        let mut reports = Vec::new();
+        for (path, software_id) in mapping {
+            let path = Self::normalize_path(path)?;
+            let mut report = SoftwareImport {
+                software_id: software_id.clone(),
+                path: path.clone(),
+                imported: 0,
+                skipped: 0,
+            };
+
+            for commit in Self::commits_touching(&repo, &path)? {
+                // The file was deleted in this commit
+                let Ok(content) = repo.run(&["show", &format!("{}:{}", commit.hash, path)]) else {
+                    report.skipped += 1;
+                    continue;
+                };
+
+                if Self::is_exported_version(storage, software_id, &commit.message) {
+                    report.skipped += 1;
+                    continue;
+                }
+
+                let note = Some(commit.message.trim().to_string()).filter(|m| !m.is_empty());
+                match storage.import_version(software_id, &content, note, commit.timestamp, &commit.hash)? {
+                    Some(_) => report.imported += 1,
+                    None => report.skipped += 1,
+                }
+            }
+            reports.push(report);
+        }
+
+        let versions_imported = reports.iter().map(|r| r.imported).sum();
+        if versions_imported > 0 {
+            log::info!("Imported {} versions from {:?}", versions_imported, repo_dir);
+        }
+
+        Ok(GitImportReport {
+            repo_dir: repo_dir.to_string_lossy().to_string(),
+            versions_imported,
+            software: reports,
+        })
+    }
+
+    // Commits touching `path`, oldest first
+    fn commits_touching(repo: &GitRepo, path: &str) -> Result<Vec<CommitInfo>> {
+        let format = format!("--format=%H{0}%at{0}%B{1}", FIELD_SEPARATOR, RECORD_SEPARATOR);
+        let log = repo.run(&["log", "--reverse", &format, "--", path])?;
+
+        log.split(RECORD_SEPARATOR)
+            .map(|record| record.trim_start_matches('\n'))
+            .filter(|record| !record.is_empty())
+            .map(|record| {
+                let mut fields = record.splitn(3, FIELD_SEPARATOR);
+                let (Some(hash), Some(time), Some(message)) = (fields.next(), fields.next(), fields.next()) else {
+                    return Err(anyhow!("Unexpected git log output"));
+                };
+                let seconds: i64 = time.parse().context("Invalid commit time")?;
+                Ok(CommitInfo {
+                    hash: hash.to_string(),
+                    timestamp: DateTime::from_timestamp(seconds, 0).context("Invalid commit time")?,
+                    message: message.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    // Commits written by the exporter point back at a version that may still exist
+    fn is_exported_version(storage: &VersionStorage, software_id: &str, message: &str) -> bool {
+        let prefix = format!("{}: ", VERSION_TRAILER);
+        message
+            .lines()
+            .filter_map(|line| line.strip_prefix(&prefix))
+            .any(|id| storage.get_version(software_id, id.trim()).is_ok())
+    }
+
+    // Repository-relative path with forward slashes
+    fn normalize_path(path: &str) -> Result<String> {
+        let path = path.trim().replace('\\', "/");
+        let path = path.trim_start_matches("./");
+        if path.is_empty() || path.starts_with('/') || path.split('/').any(|part| part == "..") {
+            return Err(anyhow!("{:?} must be a path relative to the repository root", path));
+        }
+        Ok(path.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_import_keeps_commit_times_and_is_idempotent() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = VersionStorage::with_base_path(dir.path().join("versions")).unwrap();
+        let repo = GitRepo::open_or_init(&dir.path().join("dotfiles")).unwrap();
+
+        let first = DateTime::from_timestamp(1_600_000_000, 0).unwrap();
+        let second = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        for (content, message, time) in [("export A=1\n", "Add zshrc", first), ("export A=2\n", "Bump A\n\nDetails", second)] {
+            fs::write(repo.root().join(".zshrc"), content).unwrap();
+            repo.run(&["add", ".zshrc"]).unwrap();
+            repo.commit(message, time).unwrap();
+        }
+
+        let mapping = BTreeMap::from([("./.zshrc".to_string(), "zsh".to_string())]);
+        let report = GitImporter::import_history(&storage, repo.root(), &mapping).unwrap();
+        assert_eq!(report.versions_imported, 2);
+
+        let history = storage.get_history("zsh", None).unwrap();
+        assert_eq!(history[0].content, "export A=2\n");
+        assert_eq!(history[0].timestamp, second);
+        assert_eq!(history[0].note.as_deref(), Some("Bump A\n\nDetails"));
+        assert_eq!(history[1].timestamp, first);
+
+        let report = GitImporter::import_history(&storage, repo.root(), &mapping).unwrap();
+        assert_eq!(report.versions_imported, 0);
+        assert_eq!(report.software[0].skipped, 2);
+
+        // Commits whose versions were pruned since are not imported again
+        for version in &history {
+            storage.delete_version("zsh", &version.id).unwrap();
+        }
+        let report = GitImporter::import_history(&storage, repo.root(), &mapping).unwrap();
+        assert_eq!(report.versions_imported, 0);
+        assert!(storage.get_history("zsh", None).unwrap().is_empty());
+    }
+}
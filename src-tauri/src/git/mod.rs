@@ -0,0 +1,7 @@
+pub mod export;
+pub mod import;
+pub mod repo;
+
+pub use export::*;
+pub use import::*;
+pub use repo::*;
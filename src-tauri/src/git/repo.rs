@@ -0,0 +1,149 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// Identity used when the repository and the user have no git identity configured
+const FALLBACK_NAME: &str = "Config Manager";
+const FALLBACK_EMAIL: &str = "config-manager@localhost";
+
+// Thin wrapper around the `git` command line for a single repository
+pub struct GitRepo {
+    root: PathBuf,
+}
+
+impl GitRepo {
+    // Open an existing repository
+    pub fn open(root: &Path) -> Result<Self> {
+        let repo = Self { root: root.to_path_buf() };
+        repo.run(&["rev-parse", "--git-dir"])
+            .map_err(|_| anyhow!("{:?} is not a git repository", root))?;
+        Ok(repo)
+    }
+
+    // Open a repository, initializing it when the directory is missing or empty
+    pub fn open_or_init(root: &Path) -> Result<Self> {
+        let repo = Self { root: root.to_path_buf() };
+
+        if root.join(".git").exists() {
+            return Ok(repo);
+        }
+
+        let is_empty = !root.exists() || fs::read_dir(root)?.next().is_none();
+        if !is_empty {
+            return Err(anyhow!("{:?} exists and is not a git repository", root));
+        }
+
+        fs::create_dir_all(root)?;
+        repo.run(&["init", "-q"])?;
+        Ok(repo)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn run(&self, args: &[&str]) -> Result<String> {
+        self.run_with(args, &[], None)
+    }
+
+    // Run git in the repository with extra environment and optional stdin, returning stdout
+    pub fn run_with(&self, args: &[&str], env: &[(&str, String)], stdin: Option<&str>) -> Result<String> {
+        let mut command = Command::new("git");
+        command
+            .arg("-C")
+            .arg(&self.root)
+            .args(args)
+            .envs(env.iter().map(|(k, v)| (*k, v.as_str())))
+            .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let mut child = command.spawn().map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => anyhow!("git is not installed or not on PATH"),
+            _ => anyhow!("Failed to run git: {}", e),
+        })?;
+
+        if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            pipe.write_all(input.as_bytes())?;
+        }
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "git {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    pub fn has_commits(&self) -> bool {
+        self.run(&["rev-parse", "--verify", "-q", "HEAD"]).is_ok()
+    }
+
+    pub fn head(&self) -> Option<String> {
+        self.run(&["rev-parse", "HEAD"]).ok().map(|h| h.trim().to_string())
+    }
+
+    // Values of a trailer such as `Config-Version` across every commit
+    pub fn trailer_values(&self, key: &str) -> Result<HashSet<String>> {
+        if !self.has_commits() {
+            return Ok(HashSet::new());
+        }
+
+        let prefix = format!("{}: ", key);
+        let log = self.run(&["log", "--format=%B"])?;
+        Ok(log
+            .lines()
+            .filter_map(|line| line.strip_prefix(&prefix))
+            .map(|value| value.trim().to_string())
+            .collect())
+    }
+
+    // Author time of the newest commit touching `path`
+    pub fn last_commit_time(&self, path: &str) -> Result<Option<DateTime<Utc>>> {
+        if !self.has_commits() {
+            return Ok(None);
+        }
+
+        let time = self.run(&["log", "-1", "--format=%at", "--", path])?;
+        let time = time.trim();
+        if time.is_empty() {
+            return Ok(None);
+        }
+        let seconds: i64 = time.parse().context("Invalid commit time")?;
+        Ok(Some(DateTime::from_timestamp(seconds, 0).context("Invalid commit time")?))
+    }
+
+    // Refuse to run on top of changes someone else staged
+    pub fn ensure_nothing_staged(&self) -> Result<()> {
+        if self.has_commits() && self.run(&["diff", "--cached", "--quiet"]).is_err() {
+            return Err(anyhow!("{:?} has staged changes; commit or unstage them first", self.root));
+        }
+        Ok(())
+    }
+
+    // Commit staged changes, always creating a commit, dated `timestamp` for author and committer
+    pub fn commit(&self, message: &str, timestamp: DateTime<Utc>) -> Result<String> {
+        let date = format!("{} +0000", timestamp.timestamp());
+        let mut env = vec![("GIT_AUTHOR_DATE", date.clone()), ("GIT_COMMITTER_DATE", date)];
+
+        if self.run(&["config", "user.email"]).is_err() {
+            for key in ["GIT_AUTHOR_NAME", "GIT_COMMITTER_NAME"] {
+                env.push((key, FALLBACK_NAME.to_string()));
+            }
+            for key in ["GIT_AUTHOR_EMAIL", "GIT_COMMITTER_EMAIL"] {
+                env.push((key, FALLBACK_EMAIL.to_string()));
+            }
+        }
+
+        self.run_with(&["commit", "-q", "--allow-empty", "--no-verify", "-F", "-"], &env, Some(message))?;
+        self.head().context("Commit did not create HEAD")
+    }
+}
@@ -1,9 +1,12 @@
 mod commands;
+mod git;
 mod software;
 mod storage;
 mod version;
 
-use storage::{VersionStorage, PreferencesStorage};
+use software::SoftwareDefinition;
+use storage::{initialize_settings, VersionStorage, PreferencesStorage, SchemaMigrator};
+use version::{BackupScheduler, SearchIndex, VersionManager};
 use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -21,13 +24,14 @@ pub fn run() {
       // Initialize storage
       let handle = app.handle();
       
-      match VersionStorage::new(&handle) {
-        Ok(storage) => {
-          app.manage(storage);
-          log::info!("Version storage initialized successfully");
+      // Upgrade documents from older releases before anything reads them
+      match SchemaMigrator::migrate_app_data(handle) {
+        Ok(report) if !report.upgraded.is_empty() => {
+          log::info!("Upgraded {} stored documents to the current format", report.upgraded.len());
         }
+        Ok(_) => {}
         Err(e) => {
-          log::error!("Failed to initialize version storage: {}", e);
+          log::error!("Failed to upgrade stored documents: {}", e);
         }
       }
       
@@ -41,6 +45,70 @@ pub fn run() {
         }
       }
       
+      // History lives in the app data directory unless the settings point elsewhere
+      let location = app
+        .try_state::<PreferencesStorage>()
+        .and_then(|preferences| preferences.get_settings().ok())
+        .and_then(|settings| settings.storage_location);
+      let storage = match location {
+        Some(location) => VersionStorage::with_base_path(SoftwareDefinition::expand_path(&location).into()),
+        None => VersionStorage::new(&handle),
+      };
+      match storage {
+        Ok(storage) => {
+          app.manage(storage);
+          log::info!("Version storage initialized successfully");
+        }
+        Err(e) => {
+          log::error!("Failed to initialize version storage: {}", e);
+        }
+      }
+      
+      app.manage(SearchIndex::default());
+      
+      // Settings come first since they decide where definitions are found and whether storage
+      // is verified in the background
+      if let (Some(storage), Some(preferences)) =
+        (app.try_state::<VersionStorage>(), app.try_state::<PreferencesStorage>())
+      {
+        let settings = initialize_settings(&preferences, &storage);
+        if let Ok(ref settings) = settings {
+          commands::set_definition_dirs(settings.definition_dirs.clone());
+        }
+        match settings {
+          Ok(settings) if settings.verify_on_start => {
+            let handle = handle.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+              if let Some(storage) = handle.try_state::<VersionStorage>() {
+                match storage.verify_storage() {
+                  Ok(report) if !report.healthy => log::warn!("Version storage has integrity problems"),
+                  Ok(_) => log::info!("Version storage verified"),
+                  Err(e) => log::error!("Failed to verify version storage: {}", e),
+                }
+              }
+            });
+          }
+          Ok(_) => {}
+          Err(e) => {
+            log::error!("Failed to load app settings: {}", e);
+          }
+        }
+        
+        // Preferences own the version cap; fold in caps set on the index by older releases
+        match VersionManager::reconcile_preferences(&*storage, &*preferences) {
+          Ok(reconciled) if !reconciled.is_empty() => {
+            log::info!("Reconciled backup count with version cap for {}", reconciled.join(", "));
+          }
+          Ok(_) => {}
+          Err(e) => {
+            log::error!("Failed to reconcile backup preferences: {}", e);
+          }
+        }
+      }
+      
+      app.manage(BackupScheduler::default());
+      BackupScheduler::start(handle.clone());
+      
       Ok(())
     })
     .invoke_handler(tauri::generate_handler![
@@ -52,14 +120,59 @@ pub fn run() {
       commands::get_software_status,
       commands::apply_template,
       commands::get_version_history,
+      commands::query_version_history,
       commands::get_version,
+      commands::get_tagged_version,
+      commands::list_version_tags,
+      commands::add_version_tag,
+      commands::remove_version_tag,
+      commands::add_version_label,
+      commands::remove_version_label,
+      commands::set_version_pinned,
       commands::restore_version,
+      commands::restore_version_merge,
+      commands::restore_partial,
       commands::delete_version,
       commands::create_backup,
+      commands::get_backup_status,
+      commands::set_backup_schedule,
+      commands::run_backups_now,
       commands::set_max_versions,
       commands::get_max_versions,
+      commands::get_retention_policy,
+      commands::set_retention_policy,
+      commands::prune_preview,
+      commands::prune_versions,
+      commands::squash_auto_saves,
+      commands::unsquash_version,
+      commands::get_encryption_status,
+      commands::enable_history_encryption,
+      commands::unlock_history,
+      commands::lock_history,
+      commands::rotate_history_key,
+      commands::disable_history_encryption,
+      commands::get_secret_policy,
+      commands::set_secret_policy,
+      commands::enable_secret_encryption,
+      commands::scan_config_secrets,
+      commands::get_drift_report,
+      commands::verify_storage,
+      commands::get_storage_stats,
+      commands::compact_storage,
+      commands::migrate_storage,
+      commands::rebuild_version_index,
+      commands::diff_versions,
+      commands::blame,
+      commands::export_history_to_git,
+      commands::import_git_history,
+      commands::search_configs,
       commands::get_preferences,
       commands::save_preferences,
+      commands::get_effective_preferences,
+      commands::reset_preferences,
+      commands::get_app_settings,
+      commands::save_app_settings,
+      commands::reset_app_settings,
       commands::read_config,
       commands::save_config,
       commands::config_exists,
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::storage::SecretFinding;
+
 // Software category types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -140,6 +142,15 @@ pub struct ConfigVersion {
     pub note: Option<String>,
     pub is_auto_save: bool,
     pub checksum: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    // Secrets detected when this version was saved
+    #[serde(default)]
+    pub secrets: Vec<SecretFinding>,
 }
 
 // Software status
@@ -1,6 +1,7 @@
 use anyhow::{Context, Result};
 use serde_json::Value;
 use std::fs;
+use std::io;
 use std::path::Path;
 
 use super::definitions::{ConfigFormat, SoftwareDefinition};
@@ -200,6 +201,11 @@ impl ConfigManager {
         }
     }
     
+    // Parse raw content using the software's configured format
+    pub fn parse_content(software: &SoftwareDefinition, content: &str) -> Result<Value> {
+        Self::get_parser(&software.format).parse(content)
+    }
+    
     // Read configuration file
     pub fn read_config(software: &SoftwareDefinition) -> Result<(String, Value)> {
         let paths = software.get_config_path()
@@ -220,11 +226,22 @@ impl ConfigManager {
             }
         }
         
-        Err(anyhow::anyhow!("Configuration file not found"))
+        Err(io::Error::new(io::ErrorKind::NotFound, "Configuration file not found").into())
+    }
+    
+    // Serialize a value using the software's configured format
+    pub fn serialize_content(software: &SoftwareDefinition, value: &Value) -> Result<String> {
+        Self::get_parser(&software.format).serialize(value)
     }
     
     // Write configuration file
     pub fn write_config(software: &SoftwareDefinition, value: &Value) -> Result<()> {
+        let content = Self::serialize_content(software, value)?;
+        Self::write_raw_config(software, &content)
+    }
+    
+    // Write raw content to the primary configuration file
+    pub fn write_raw_config(software: &SoftwareDefinition, content: &str) -> Result<()> {
         let paths = software.get_config_path()
             .context("No config path for current platform")?;
         
@@ -241,9 +258,6 @@ impl ConfigManager {
             fs::create_dir_all(parent)?;
         }
         
-        let parser = Self::get_parser(&software.format);
-        let content = parser.serialize(value)?;
-        
         fs::write(path, content)
             .context(format!("Failed to write config file: {:?}", path))?;
         
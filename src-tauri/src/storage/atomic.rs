@@ -0,0 +1,36 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// Path of the pending copy written before it replaces `path`
+pub fn pending_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".pending");
+    path.with_file_name(name)
+}
+
+// Replace `path` with `contents` so readers see either the old or the new file, never a partial one.
+// The data is flushed to a pending file first and then renamed over the target.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let pending = pending_path(path);
+
+    let mut file = File::create(&pending)?;
+    file.write_all(contents.as_ref())?;
+    file.sync_all()?;
+    drop(file);
+
+    commit_pending(path)
+}
+
+// Move a fully written pending file into place
+pub fn commit_pending(path: &Path) -> io::Result<()> {
+    fs::rename(pending_path(path), path)?;
+
+    // Persist the rename itself; directories cannot be opened for syncing on Windows
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        File::open(parent)?.sync_all()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,281 @@
+use anyhow::{anyhow, Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::atomic::write_atomic;
+use super::{validate_digest, ChecksumAlgorithm, KeyRing, StorageError, VersionStorage};
+
+// How the blobs of an encrypted history are accessed
+#[derive(Clone)]
+pub enum BlobCipher {
+    // No session key; encrypted blobs can be neither read nor written
+    Locked(String),
+    Unlocked(Arc<KeyRing>),
+}
+
+// Content-addressed store of gzip-compressed blobs keyed by their digest. Plain blobs are
+// `<sha256>.gz`; encrypted ones are `<keyed digest>.enc` holding the encrypted gzip data, so
+// their names reveal nothing about the content.
+pub struct BlobStore {
+    root: PathBuf,
+    cipher: Option<BlobCipher>,
+}
+
+impl BlobStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root, cipher: None }
+    }
+
+    pub fn encrypted(root: PathBuf, cipher: BlobCipher) -> Self {
+        Self { root, cipher: Some(cipher) }
+    }
+
+    // Get the file path of a blob, preferring its encrypted form
+    pub(super) fn blob_path(&self, digest: &str) -> Result<PathBuf> {
+        let sealed = self.sealed_path(digest)?;
+        if sealed.exists() {
+            return Ok(sealed);
+        }
+        self.plain_path(digest)
+    }
+
+    fn plain_path(&self, digest: &str) -> Result<PathBuf> {
+        validate_digest(digest)?;
+        Ok(self.root.join(format!("{}.gz", digest)))
+    }
+
+    fn sealed_path(&self, digest: &str) -> Result<PathBuf> {
+        validate_digest(digest)?;
+        Ok(self.root.join(format!("{}.enc", digest)))
+    }
+
+    // Whether blobs are encrypted and no key is available
+    pub fn is_locked(&self) -> bool {
+        matches!(self.cipher, Some(BlobCipher::Locked(_)))
+    }
+
+    fn keys(&self) -> Result<&Arc<KeyRing>> {
+        match &self.cipher {
+            Some(BlobCipher::Unlocked(keys)) => Ok(keys),
+            Some(BlobCipher::Locked(software_id)) => Err(StorageError::HistoryLocked {
+                software_id: software_id.clone(),
+            }.into()),
+            None => Err(anyhow!("Blob store is not encrypted")),
+        }
+    }
+
+    // Digest naming the blob of some content: keyed when blobs are encrypted
+    pub fn digest(&self, content: &str) -> Result<String> {
+        match self.cipher {
+            Some(_) => Ok(self.keys()?.digest(content)),
+            None => Ok(VersionStorage::calculate_checksum(content)),
+        }
+    }
+
+    // Algorithm of the digests this store hands out, recorded as the version checksum
+    pub fn checksum_algorithm(&self) -> ChecksumAlgorithm {
+        match self.cipher {
+            Some(_) => ChecksumAlgorithm::HmacSha256,
+            None => ChecksumAlgorithm::Sha256,
+        }
+    }
+
+    // Store content and return its digest; identical content is only written once
+    pub fn put(&self, content: &str) -> Result<String> {
+        let digest = self.digest(content)?;
+        let (path, keys) = match self.cipher {
+            Some(_) => (self.sealed_path(&digest)?, Some(self.keys()?)),
+            None => (self.plain_path(&digest)?, None),
+        };
+        if path.exists() {
+            return Ok(digest);
+        }
+
+        fs::create_dir_all(&self.root)?;
+
+        let mut compressed = compress(content, Compression::default())?;
+        if let Some(keys) = keys {
+            compressed = keys.seal(&digest, &compressed)?;
+        }
+
+        // Never leave a truncated blob behind if the write is interrupted
+        write_atomic(&path, compressed)?;
+
+        Ok(digest)
+    }
+
+    // Load, decrypt if needed, and decompress a blob
+    pub fn get(&self, digest: &str) -> Result<String> {
+        let sealed = self.sealed_path(digest)?;
+        let compressed = if sealed.exists() {
+            self.keys()?.open(digest, &fs::read(&sealed)?)?
+        } else {
+            fs::read(self.plain_path(digest)?).context(format!("Blob {} not found", digest))?
+        };
+
+        decompress(&compressed, digest)
+    }
+
+    // Whether a blob is stored in its encrypted form
+    pub(super) fn is_sealed(&self, digest: &str) -> bool {
+        self.sealed_path(digest).is_ok_and(|path| path.exists())
+    }
+
+    // Make sure an encrypted blob uses the current key; returns whether it was rewritten
+    pub(super) fn reseal(&self, digest: &str) -> Result<bool> {
+        let keys = self.keys()?;
+        let sealed = self.sealed_path(digest)?;
+        let data = fs::read(&sealed)?;
+        if keys.is_current(&data) {
+            return Ok(false);
+        }
+
+        write_atomic(&sealed, keys.seal(digest, &keys.open(digest, &data)?)?)?;
+        Ok(true)
+    }
+
+    // Encrypt a plain blob under its keyed digest, returning that digest. The plain blob is
+    // kept until nothing refers to it anymore.
+    pub(super) fn seal_plain(&self, digest: &str) -> Result<String> {
+        let compressed = fs::read(self.plain_path(digest)?)?;
+        let keyed = self.digest(&decompress(&compressed, digest)?)?;
+        let sealed = self.sealed_path(&keyed)?;
+        if !sealed.exists() {
+            write_atomic(&sealed, self.keys()?.seal(&keyed, &compressed)?)?;
+        }
+        Ok(keyed)
+    }
+
+    // Rewrite a blob at the best compression level if that makes it smaller, returning the
+    // bytes saved. Encrypted blobs are skipped while locked.
+    pub(super) fn recompress(&self, digest: &str) -> Result<u64> {
+        let path = self.blob_path(digest)?;
+        let sealed = path == self.sealed_path(digest)?;
+        if sealed && self.is_locked() {
+            return Ok(0);
+        }
+
+        let before = fs::metadata(&path)?.len();
+        let mut data = compress(&self.get(digest)?, Compression::best())?;
+        if sealed {
+            data = self.keys()?.seal(digest, &data)?;
+        }
+        if data.len() as u64 >= before {
+            return Ok(0);
+        }
+
+        write_atomic(&path, &data)?;
+        Ok(before - data.len() as u64)
+    }
+
+    // Write the plain form of an encrypted blob under its SHA-256 digest, returning that
+    // digest. The encrypted blob is kept until nothing refers to it anymore.
+    pub(super) fn unseal(&self, digest: &str) -> Result<String> {
+        let compressed = self.keys()?.open(digest, &fs::read(self.sealed_path(digest)?)?)?;
+        let plain = VersionStorage::calculate_checksum(&decompress(&compressed, digest)?);
+        let path = self.plain_path(&plain)?;
+        if !path.exists() {
+            write_atomic(&path, compressed)?;
+        }
+        Ok(plain)
+    }
+
+    // Check whether a blob exists
+    pub fn exists(&self, digest: &str) -> bool {
+        self.sealed_path(digest).is_ok_and(|path| path.exists())
+            || self.plain_path(digest).is_ok_and(|path| path.exists())
+    }
+
+    // List the digests of all stored blobs
+    pub fn list(&self) -> Result<Vec<String>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut digests = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if let Some(digest) = name.strip_suffix(".gz").or_else(|| name.strip_suffix(".enc")) {
+                digests.push(digest.to_string());
+            }
+        }
+
+        // A blob caught mid-conversion has both forms
+        digests.sort();
+        digests.dedup();
+        Ok(digests)
+    }
+
+    // Remove a blob if it exists
+    pub fn remove(&self, digest: &str) -> Result<()> {
+        self.remove_file(self.sealed_path(digest)?)?;
+        self.remove_file(self.plain_path(digest)?)
+    }
+
+    fn remove_file(&self, path: PathBuf) -> Result<()> {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+fn decompress(compressed: &[u8], digest: &str) -> Result<String> {
+    let mut content = String::new();
+    GzDecoder::new(compressed)
+        .read_to_string(&mut content)
+        .context(format!("Failed to decompress blob {}", digest))?;
+    Ok(content)
+}
+
+fn compress(content: &str, level: Compression) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), level);
+    encoder.write_all(content.as_bytes())?;
+    Ok(encoder.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_content_is_stored_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = BlobStore::new(dir.path().join("blobs"));
+
+        let first = blobs.put("export A=1\n").unwrap();
+        let second = blobs.put("export A=1\n").unwrap();
+        let other = blobs.put("export A=2\n").unwrap();
+
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert_eq!(blobs.list().unwrap().len(), 2);
+        assert_eq!(fs::read_dir(dir.path().join("blobs")).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_blobs_round_trip_through_gzip() {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = BlobStore::new(dir.path().join("blobs"));
+        let content = "set -o vi\n".repeat(200);
+
+        let digest = blobs.put(&content).unwrap();
+        let path = blobs.blob_path(&digest).unwrap();
+        assert_eq!(path.file_name().unwrap().to_string_lossy(), format!("{}.gz", digest));
+
+        // Stored compressed, read back unchanged
+        let stored = fs::read(&path).unwrap();
+        assert!(stored.starts_with(&[0x1f, 0x8b]));
+        assert!(stored.len() < content.len());
+        assert_eq!(blobs.get(&digest).unwrap(), content);
+
+        blobs.remove(&digest).unwrap();
+        assert!(!blobs.exists(&digest));
+        assert!(blobs.get(&digest).is_err());
+    }
+}
@@ -0,0 +1,642 @@
+use anyhow::{anyhow, Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use zeroize::Zeroizing;
+
+use super::atomic::write_atomic;
+use super::schema::{read_document, write_document, DocumentKind};
+use super::{BlobCipher, ChecksumAlgorithm, StorageError, VersionLayout, VersionStorage};
+
+// Per-software file whose presence marks its history as encrypted
+pub(super) const ENCRYPTION_FILE: &str = "encryption.json";
+
+pub const MIN_PASSPHRASE_LENGTH: usize = 8;
+
+const KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 16;
+// Hex characters of a key id
+const KEY_ID_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+// Leading bytes of every encrypted blob, followed by the key id and nonce
+const BLOB_MAGIC: &[u8] = b"CME1";
+// Associated data binding the wrapped digest key to its purpose
+const DIGEST_KEY_ID: &str = "digest";
+
+// Argon2id parameters used to derive the key that wraps the data keys
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub salt: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    fn generate() -> Self {
+        let mut salt = [0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            salt: to_hex(&salt),
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+
+    fn derive_key(&self, passphrase: &str) -> Result<Zeroizing<[u8; KEY_LENGTH]>> {
+        let salt = from_hex(&self.salt)?;
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(KEY_LENGTH))
+            .map_err(|e| anyhow!("Invalid key derivation parameters: {}", e))?;
+
+        let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut *key)
+            .map_err(|e| anyhow!("Failed to derive key: {}", e))?;
+        Ok(key)
+    }
+}
+
+// A data key encrypted with the passphrase-derived key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WrappedKey {
+    pub id: String,
+    pub nonce: String,
+    pub key: String,
+}
+
+// What the passphrase of a software protects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncryptionScope {
+    // Every version blob
+    #[default]
+    History,
+    // Only secrets set aside by the `encrypt` secret policy; blobs stay readable
+    Secrets,
+}
+
+// Contents of `encryption.json`. Blobs are encrypted with random data keys and only the data
+// keys depend on the passphrase, so changing it never requires touching the blobs themselves.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionHeader {
+    #[serde(default)]
+    pub scope: EncryptionScope,
+    pub kdf: KdfParams,
+    // The current key first; older keys remain only while blobs still use them
+    pub keys: Vec<WrappedKey>,
+    // Key of the digests naming encrypted blobs. It survives rotation, so blob names stay put.
+    pub digest_key: WrappedKey,
+    pub key_created_at: DateTime<Utc>,
+}
+
+impl EncryptionHeader {
+    // Wrap every key of the ring with a key derived from the passphrase and a fresh salt
+    fn seal(keys: &KeyRing, passphrase: &str, scope: EncryptionScope) -> Result<Self> {
+        let kdf = KdfParams::generate();
+        let wrapping_key = kdf.derive_key(passphrase)?;
+        let wrapping = cipher(&wrapping_key);
+
+        let wrapped = keys.keys
+            .iter()
+            .map(|(id, key)| wrap_key(&wrapping, id, key))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            scope,
+            kdf,
+            keys: wrapped,
+            digest_key: wrap_key(&wrapping, DIGEST_KEY_ID, &keys.digest_key)?,
+            key_created_at: Utc::now(),
+        })
+    }
+
+    // Unwrap the data keys, failing if the passphrase is wrong
+    fn open(&self, passphrase: &str) -> Result<KeyRing> {
+        let wrapping_key = self.kdf.derive_key(passphrase)?;
+        let wrapping = cipher(&wrapping_key);
+
+        let keys = self.keys
+            .iter()
+            .map(|wrapped| Ok((wrapped.id.clone(), unwrap_key(&wrapping, wrapped)?)))
+            .collect::<Result<Vec<_>>>()?;
+        if keys.is_empty() || self.digest_key.id != DIGEST_KEY_ID {
+            return Err(anyhow!("Malformed encryption header"));
+        }
+
+        Ok(KeyRing {
+            keys,
+            digest_key: unwrap_key(&wrapping, &self.digest_key)?,
+        })
+    }
+}
+
+fn wrap_key(wrapping: &XChaCha20Poly1305, id: &str, key: &[u8; KEY_LENGTH]) -> Result<WrappedKey> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let sealed = wrapping
+        .encrypt(&nonce, Payload { msg: &key[..], aad: id.as_bytes() })
+        .map_err(|_| anyhow!("Failed to wrap data key"))?;
+    Ok(WrappedKey {
+        id: id.to_string(),
+        nonce: to_hex(&nonce),
+        key: to_hex(&sealed),
+    })
+}
+
+fn unwrap_key(wrapping: &XChaCha20Poly1305, wrapped: &WrappedKey) -> Result<Zeroizing<[u8; KEY_LENGTH]>> {
+    let nonce = from_hex(&wrapped.nonce)?;
+    if nonce.len() != NONCE_LENGTH {
+        return Err(anyhow!("Malformed encryption header"));
+    }
+    let key = Zeroizing::new(
+        wrapping
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload { msg: &from_hex(&wrapped.key)?, aad: wrapped.id.as_bytes() },
+            )
+            .map_err(|_| anyhow!("Incorrect passphrase"))?,
+    );
+    let key: [u8; KEY_LENGTH] = key[..]
+        .try_into()
+        .map_err(|_| anyhow!("Malformed encryption header"))?;
+    Ok(Zeroizing::new(key))
+}
+
+// Unwrapped data keys of one software, the current key first
+#[derive(Clone)]
+pub struct KeyRing {
+    keys: Vec<(String, Zeroizing<[u8; KEY_LENGTH]>)>,
+    digest_key: Zeroizing<[u8; KEY_LENGTH]>,
+}
+
+impl KeyRing {
+    fn generate() -> Self {
+        let (_, digest_key) = new_key();
+        Self { keys: vec![new_key()], digest_key }
+    }
+
+    // A new current key, keeping the old ones to read blobs not yet re-encrypted
+    fn rotated(&self) -> Self {
+        let mut keys = vec![new_key()];
+        keys.extend(self.keys.iter().cloned());
+        Self { keys, digest_key: self.digest_key.clone() }
+    }
+
+    // Keyed SHA-256 of content. Unlike a plain digest, it cannot be used to confirm a guess
+    // at the content without the key.
+    pub(super) fn digest(&self, content: &str) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.digest_key[..]).expect("HMAC accepts any key length");
+        mac.update(content.as_bytes());
+        to_hex(&mac.finalize().into_bytes())
+    }
+
+    fn current_id(&self) -> &str {
+        &self.keys[0].0
+    }
+
+    // Encrypt data bound to a name, the blob digest or version id, so files cannot be swapped
+    pub(super) fn seal(&self, name: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let (id, key) = &self.keys[0];
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher(key)
+            .encrypt(&nonce, Payload { msg: plaintext, aad: name.as_bytes() })
+            .map_err(|_| anyhow!("Failed to encrypt {}", name))?;
+
+        let mut sealed = Vec::with_capacity(BLOB_MAGIC.len() + KEY_ID_LENGTH + NONCE_LENGTH + ciphertext.len());
+        sealed.extend_from_slice(BLOB_MAGIC);
+        sealed.extend_from_slice(id.as_bytes());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    pub(super) fn open(&self, name: &str, sealed: &[u8]) -> Result<Vec<u8>> {
+        let id = sealed_key_id(sealed).with_context(|| format!("{} is not encrypted", name))?;
+        let (_, key) = self.keys
+            .iter()
+            .find(|(key_id, _)| key_id == id)
+            .with_context(|| format!("{} is encrypted with an unknown key", name))?;
+
+        let start = BLOB_MAGIC.len() + KEY_ID_LENGTH;
+        let nonce = &sealed[start..start + NONCE_LENGTH];
+        cipher(key)
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload { msg: &sealed[start + NONCE_LENGTH..], aad: name.as_bytes() },
+            )
+            .map_err(|_| anyhow!("{} failed authentication", name))
+    }
+
+    pub(super) fn is_current(&self, sealed: &[u8]) -> bool {
+        sealed_key_id(sealed) == Some(self.current_id())
+    }
+}
+
+fn sealed_key_id(sealed: &[u8]) -> Option<&str> {
+    let header = BLOB_MAGIC.len() + KEY_ID_LENGTH + NONCE_LENGTH;
+    if sealed.len() < header || !sealed.starts_with(BLOB_MAGIC) {
+        return None;
+    }
+    std::str::from_utf8(&sealed[BLOB_MAGIC.len()..BLOB_MAGIC.len() + KEY_ID_LENGTH]).ok()
+}
+
+fn new_key() -> (String, Zeroizing<[u8; KEY_LENGTH]>) {
+    let mut id = [0u8; KEY_ID_LENGTH / 2];
+    OsRng.fill_bytes(&mut id);
+    let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+    OsRng.fill_bytes(&mut *key);
+    (to_hex(&id), key)
+}
+
+fn cipher(key: &[u8; KEY_LENGTH]) -> XChaCha20Poly1305 {
+    XChaCha20Poly1305::new(key.into())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return Err(anyhow!("Malformed encryption header"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| anyhow!("Malformed encryption header")))
+        .collect()
+}
+
+// Data keys of the software unlocked in this session, dropped when locked or on exit
+#[derive(Default)]
+pub struct SessionKeys {
+    keys: Mutex<HashMap<String, Arc<KeyRing>>>,
+}
+
+impl SessionKeys {
+    fn get(&self, software_id: &str) -> Option<Arc<KeyRing>> {
+        self.keys.lock().unwrap_or_else(|e| e.into_inner()).get(software_id).cloned()
+    }
+
+    fn insert(&self, software_id: &str, keys: KeyRing) -> Arc<KeyRing> {
+        let keys = Arc::new(keys);
+        self.keys
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(software_id.to_string(), keys.clone());
+        keys
+    }
+
+    fn remove(&self, software_id: &str) -> bool {
+        self.keys.lock().unwrap_or_else(|e| e.into_inner()).remove(software_id).is_some()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionStatus {
+    pub software_id: String,
+    // Whether version blobs are encrypted
+    pub encrypted: bool,
+    // What the passphrase protects, if one is set
+    pub scope: Option<EncryptionScope>,
+    // Whether encrypted data can be read in this session
+    pub unlocked: bool,
+    pub key_created_at: Option<DateTime<Utc>>,
+}
+
+impl VersionStorage {
+    pub fn encryption_status(&self, software_id: &str) -> Result<EncryptionStatus> {
+        let header = self.load_encryption_header(software_id)?;
+        Ok(EncryptionStatus {
+            software_id: software_id.to_string(),
+            encrypted: header.as_ref().is_some_and(|h| h.scope == EncryptionScope::History),
+            scope: header.as_ref().map(|h| h.scope),
+            unlocked: header.is_none() || self.session_keys().get(software_id).is_some(),
+            key_created_at: header.map(|h| h.key_created_at),
+        })
+    }
+
+    // Whether the history of a software is encrypted and not unlocked in this session
+    pub fn is_history_locked(&self, software_id: &str) -> Result<bool> {
+        Ok(self.blob_cipher(software_id)?.is_some_and(|cipher| matches!(cipher, BlobCipher::Locked(_))))
+    }
+
+    // Encrypt every stored version of a software and every version saved from now on. The
+    // history stays unlocked for the rest of the session. If only secrets were encrypted so
+    // far, the passphrase must be the one already set.
+    pub fn enable_encryption(&self, software_id: &str, passphrase: &str) -> Result<()> {
+        validate_passphrase(passphrase)?;
+        let _lock = self.lock()?;
+        let existing = self.load_encryption_header(software_id)?;
+        if existing.as_ref().is_some_and(|h| h.scope == EncryptionScope::History) {
+            return Err(anyhow!("History of {} is already encrypted", software_id));
+        }
+
+        // Inline versions keep their content in the record, where it cannot be encrypted
+        let index = self.load_index(software_id)?;
+        if index.versions.iter().chain(&index.squashed).any(|v| v.layout == VersionLayout::Inline) {
+            return Err(anyhow!(
+                "Some versions of {} are still in the legacy format; run a storage check first",
+                software_id
+            ));
+        }
+
+        let (header, keys) = match existing {
+            Some(header) => {
+                let keys = header.open(passphrase)?;
+                (EncryptionHeader { scope: EncryptionScope::History, ..header }, keys)
+            }
+            None => {
+                let keys = KeyRing::generate();
+                (EncryptionHeader::seal(&keys, passphrase, EncryptionScope::History)?, keys)
+            }
+        };
+        fs::create_dir_all(self.get_software_path(software_id)?)?;
+        self.save_encryption_header(software_id, &header)?;
+        let keys = self.session_keys().insert(software_id, keys);
+        self.reseal(software_id, keys)?;
+
+        log::info!("Enabled encryption of {} history", software_id);
+        Ok(())
+    }
+
+    // Set a passphrase used only for secrets the `encrypt` secret policy sets aside
+    pub fn enable_secret_encryption(&self, software_id: &str, passphrase: &str) -> Result<()> {
+        validate_passphrase(passphrase)?;
+        let _lock = self.lock()?;
+        if self.load_encryption_header(software_id)?.is_some() {
+            return Err(anyhow!("A passphrase is already set for {}", software_id));
+        }
+
+        let keys = KeyRing::generate();
+        fs::create_dir_all(self.get_software_path(software_id)?)?;
+        self.save_encryption_header(software_id, &EncryptionHeader::seal(&keys, passphrase, EncryptionScope::Secrets)?)?;
+        self.session_keys().insert(software_id, keys);
+        Ok(())
+    }
+
+    // Make encrypted history readable and writable until it is locked or the app exits
+    pub fn unlock_history(&self, software_id: &str, passphrase: &str) -> Result<()> {
+        let _lock = self.lock()?;
+        let header = self.require_encryption_header(software_id)?;
+        let keys = self.session_keys().insert(software_id, header.open(passphrase)?);
+
+        // Finish an enable or rotation that was interrupted part way
+        self.reseal(software_id, keys)
+    }
+
+    // Forget the session key; returns whether the history was unlocked
+    pub fn lock_history(&self, software_id: &str) -> Result<bool> {
+        let _lock = self.lock()?;
+        Ok(self.session_keys().remove(software_id))
+    }
+
+    // Switch to a new passphrase and a new data key, re-encrypting every blob
+    pub fn rotate_encryption_key(&self, software_id: &str, passphrase: &str, new_passphrase: &str) -> Result<()> {
+        validate_passphrase(new_passphrase)?;
+        let _lock = self.lock()?;
+        let header = self.require_encryption_header(software_id)?;
+        let keys = header.open(passphrase)?.rotated();
+
+        // Old keys stay in the header until every blob has moved to the new one
+        self.save_encryption_header(software_id, &EncryptionHeader::seal(&keys, new_passphrase, header.scope)?)?;
+        let keys = self.session_keys().insert(software_id, keys);
+        self.reseal(software_id, keys)?;
+
+        log::info!("Rotated the encryption key of {} history", software_id);
+        Ok(())
+    }
+
+    // Decrypt every blob and store history in the clear again. The passphrase is kept while
+    // versions still hold encrypted secrets.
+    pub fn disable_encryption(&self, software_id: &str, passphrase: &str) -> Result<()> {
+        let _lock = self.lock()?;
+        let header = self.require_encryption_header(software_id)?;
+        let keys = Arc::new(header.open(passphrase)?);
+
+        // Plain blobs are named by their SHA-256 digest again
+        let blobs = self.blob_store(software_id, Some(BlobCipher::Unlocked(keys.clone())))?;
+        let mut renamed = HashMap::new();
+        for digest in blobs.list()? {
+            if blobs.is_sealed(&digest) {
+                renamed.insert(digest.clone(), blobs.unseal(&digest)?);
+            }
+        }
+        self.rename_blobs(software_id, &renamed, ChecksumAlgorithm::Sha256)?;
+        for digest in renamed.keys() {
+            blobs.remove(digest)?;
+        }
+
+        let sealed_secrets = self.sealed_secret_files(software_id)?.len();
+        if sealed_secrets > 0 {
+            if header.scope == EncryptionScope::Secrets {
+                return Err(anyhow!(
+                    "{} versions of {} hold encrypted secrets; the passphrase is needed until they are deleted",
+                    sealed_secrets, software_id
+                ));
+            }
+            self.save_encryption_header(software_id, &EncryptionHeader { scope: EncryptionScope::Secrets, ..header })?;
+            log::info!("Disabled encryption of {} history; secrets stay encrypted", software_id);
+            return Ok(());
+        }
+
+        // Removed last, so an interrupted run still has the keys for the remaining blobs
+        fs::remove_file(self.encryption_file(software_id)?)?;
+        self.session_keys().remove(software_id);
+
+        log::info!("Disabled encryption of {} history", software_id);
+        Ok(())
+    }
+
+    // How blobs of a software are read and written in this session
+    pub(super) fn blob_cipher(&self, software_id: &str) -> Result<Option<BlobCipher>> {
+        // Checked first to avoid parsing the header on every blob access of plain history
+        if !self.encryption_file(software_id)?.exists() {
+            return Ok(None);
+        }
+        if self.require_encryption_header(software_id)?.scope != EncryptionScope::History {
+            return Ok(None);
+        }
+        Ok(Some(match self.session_keys().get(software_id) {
+            Some(keys) => BlobCipher::Unlocked(keys),
+            None => BlobCipher::Locked(software_id.to_string()),
+        }))
+    }
+
+    // Keys for data encrypted under the passphrase of a software
+    pub(super) fn session_keyring(&self, software_id: &str) -> Result<Arc<KeyRing>> {
+        if !self.encryption_file(software_id)?.exists() {
+            return Err(anyhow!("No passphrase is set for {}", software_id));
+        }
+        self.session_keys()
+            .get(software_id)
+            .ok_or_else(|| StorageError::HistoryLocked { software_id: software_id.to_string() }.into())
+    }
+
+    // Encrypt every blob and sealed secret with the current key, then drop keys nothing uses anymore
+    fn reseal(&self, software_id: &str, keys: Arc<KeyRing>) -> Result<()> {
+        let mut header = self.require_encryption_header(software_id)?;
+        let mut resealed = 0;
+
+        if header.scope == EncryptionScope::History {
+            // Plain blobs move to their keyed digest; the index follows before they are removed
+            let blobs = self.blob_store(software_id, Some(BlobCipher::Unlocked(keys.clone())))?;
+            let mut renamed = HashMap::new();
+            for digest in blobs.list()? {
+                if !blobs.is_sealed(&digest) {
+                    renamed.insert(digest.clone(), blobs.seal_plain(&digest)?);
+                    resealed += 1;
+                } else if blobs.reseal(&digest)? {
+                    resealed += 1;
+                }
+            }
+            self.rename_blobs(software_id, &renamed, ChecksumAlgorithm::HmacSha256)?;
+            for digest in renamed.keys() {
+                blobs.remove(digest)?;
+            }
+        }
+        for (version_id, path) in self.sealed_secret_files(software_id)? {
+            let sealed = fs::read(&path)?;
+            if !keys.is_current(&sealed) {
+                write_atomic(&path, keys.seal(&version_id, &keys.open(&version_id, &sealed)?)?)?;
+                resealed += 1;
+            }
+        }
+        if resealed > 0 {
+            log::info!("Encrypted {} files of {} with the current key", resealed, software_id);
+        }
+
+        if header.keys.len() > 1 {
+            header.keys.truncate(1);
+            self.save_encryption_header(software_id, &header)?;
+        }
+        Ok(())
+    }
+
+    // Point versions at the new digests of their blobs, which double as their checksums
+    fn rename_blobs(
+        &self,
+        software_id: &str,
+        renamed: &HashMap<String, String>,
+        algorithm: ChecksumAlgorithm,
+    ) -> Result<()> {
+        if renamed.is_empty() {
+            return Ok(());
+        }
+
+        let mut index = self.load_index(software_id)?;
+        for metadata in index.versions.iter_mut().chain(index.squashed.iter_mut()) {
+            if let Some(digest) = renamed.get(&metadata.checksum) {
+                metadata.checksum = digest.clone();
+                metadata.checksum_algorithm = algorithm;
+                self.write_record(software_id, metadata)?;
+            }
+        }
+        self.save_index(software_id, &index)
+    }
+
+    // Version ids and paths of stored encrypted secrets
+    pub(super) fn sealed_secret_files(&self, software_id: &str) -> Result<Vec<(String, PathBuf)>> {
+        let dir = self.secrets_dir(software_id)?;
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let version_id = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".enc"))
+                .map(str::to_string);
+            if let Some(version_id) = version_id {
+                files.push((version_id, path));
+            }
+        }
+        Ok(files)
+    }
+
+    fn encryption_file(&self, software_id: &str) -> Result<PathBuf> {
+        Ok(self.get_software_path(software_id)?.join(ENCRYPTION_FILE))
+    }
+
+    fn load_encryption_header(&self, software_id: &str) -> Result<Option<EncryptionHeader>> {
+        let path = self.encryption_file(software_id)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let header = read_document(DocumentKind::EncryptionHeader, &path)
+            .context(format!("Failed to parse encryption header of {}", software_id))?;
+        Ok(Some(header))
+    }
+
+    fn require_encryption_header(&self, software_id: &str) -> Result<EncryptionHeader> {
+        self.load_encryption_header(software_id)?
+            .ok_or_else(|| anyhow!("History of {} is not encrypted", software_id))
+    }
+
+    fn save_encryption_header(&self, software_id: &str, header: &EncryptionHeader) -> Result<()> {
+        write_document(DocumentKind::EncryptionHeader, &self.encryption_file(software_id)?, header)
+    }
+}
+
+fn validate_passphrase(passphrase: &str) -> Result<()> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LENGTH {
+        return Err(anyhow!("Passphrase must be at least {} characters", MIN_PASSPHRASE_LENGTH));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::StorageError;
+
+    #[test]
+    fn test_encrypted_history_needs_unlocking() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = VersionStorage::with_base_path(dir.path().to_path_buf()).unwrap();
+        let first = storage.save_version("ssh", "Host *\n", None, false).unwrap();
+
+        storage.enable_encryption("ssh", "correct horse").unwrap();
+        // Encrypted blobs and checksums use a keyed digest instead of the plain SHA-256
+        let keyed = storage.get_version("ssh", &first.id).unwrap().checksum.unwrap();
+        assert_ne!(Some(&keyed), first.checksum.as_ref());
+        let blobs = dir.path().join("ssh").join("blobs");
+        let files: Vec<_> = fs::read_dir(&blobs).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(files, vec![std::ffi::OsString::from(format!("{}.enc", keyed))]);
+
+        storage.save_version("ssh", "Host *\n  User me\n", None, false).unwrap();
+        assert!(storage.lock_history("ssh").unwrap());
+        let error = storage.get_version("ssh", &first.id).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(StorageError::HistoryLocked { .. })));
+        assert!(storage.save_version("ssh", "x\n", None, true).is_err());
+
+        assert!(storage.unlock_history("ssh", "wrong horse").is_err());
+        storage.unlock_history("ssh", "correct horse").unwrap();
+        assert_eq!(storage.get_version("ssh", &first.id).unwrap().content, "Host *\n");
+
+        storage.rotate_encryption_key("ssh", "correct horse", "battery staple").unwrap();
+        storage.lock_history("ssh").unwrap();
+        assert!(storage.unlock_history("ssh", "correct horse").is_err());
+        storage.unlock_history("ssh", "battery staple").unwrap();
+        assert_eq!(storage.get_history("ssh", None).unwrap().len(), 2);
+        assert!(storage.verify_storage().unwrap().healthy);
+
+        storage.disable_encryption("ssh", "battery staple").unwrap();
+        assert!(!storage.encryption_status("ssh").unwrap().encrypted);
+        let plain = storage.get_version("ssh", &first.id).unwrap();
+        assert_eq!(plain.content, "Host *\n");
+        assert_eq!(plain.checksum, first.checksum);
+        assert!(storage.verify_storage().unwrap().healthy);
+    }
+}
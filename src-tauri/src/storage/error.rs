@@ -0,0 +1,23 @@
+use thiserror::Error;
+
+// Typed storage failures that callers may want to distinguish from generic I/O errors
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("Checksum mismatch for version {version_id} of {software_id}: expected {expected}, found {actual}")]
+    ChecksumMismatch {
+        software_id: String,
+        version_id: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("Invalid {kind}: {value:?}")]
+    InvalidId { kind: String, value: String },
+    #[error("Storage is locked by another operation: {path}")]
+    Locked { path: String },
+    #[error("History of {software_id} is encrypted; unlock it with its passphrase first")]
+    HistoryLocked { software_id: String },
+    #[error("{path} was written by a newer version of the app (format {found}, supported {supported}); update the app to open it")]
+    UnsupportedFormat { path: String, found: u32, supported: u32 },
+    #[error("Version {version_id} is pinned; unpin it first")]
+    Pinned { version_id: String },
+}
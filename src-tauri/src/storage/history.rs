@@ -0,0 +1,165 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::{validate_tag, SecretFinding, SquashInfo, VersionLayout, VersionMetadata, VersionStorage};
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+// Filters and paging for a metadata-only history listing. All filters are optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryQuery {
+    // Opaque cursor from the previous page's `next_cursor`
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    // Only auto-saves (true) or only manual saves (false)
+    pub auto_save: Option<bool>,
+    pub tag: Option<String>,
+    // Case-insensitive substring of the note
+    pub note: Option<String>,
+    // Substring of the stored content; only versions passing the other filters are loaded
+    pub content: Option<String>,
+}
+
+impl HistoryQuery {
+    pub(super) fn page_size(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+// A version without its content; load content on demand with `get_version`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionSummary {
+    pub id: String,
+    pub software_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub note: Option<String>,
+    pub is_auto_save: bool,
+    pub checksum: String,
+    pub tags: Vec<String>,
+    pub labels: Vec<String>,
+    pub pinned: bool,
+    // Present when this version stands for a squashed run of auto-saves
+    pub squash: Option<SquashInfo>,
+    pub secrets: Vec<SecretFinding>,
+}
+
+impl From<&VersionMetadata> for VersionSummary {
+    fn from(metadata: &VersionMetadata) -> Self {
+        Self {
+            id: metadata.id.clone(),
+            software_id: metadata.software_id.clone(),
+            timestamp: metadata.timestamp,
+            note: metadata.note.clone(),
+            is_auto_save: metadata.is_auto_save,
+            checksum: metadata.checksum.clone(),
+            tags: metadata.tags.clone(),
+            labels: metadata.labels.clone(),
+            pinned: metadata.pinned,
+            squash: metadata.squash.clone(),
+            secrets: metadata.secrets.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryPage {
+    // Newest first
+    pub versions: Vec<VersionSummary>,
+    // Pass back as `cursor` to fetch the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+impl VersionStorage {
+    // List version metadata newest first, reading content only for content searches
+    pub fn query_history(&self, software_id: &str, query: &HistoryQuery) -> Result<HistoryPage> {
+        if let Some(ref tag) = query.tag {
+            validate_tag(tag)?;
+        }
+        let limit = query.page_size();
+        let after = query.cursor.as_deref().map(parse_cursor).transpose()?;
+        let note = query.note.as_ref().map(|n| n.to_lowercase());
+
+        let _lock = self.lock()?;
+        let index = self.load_index(software_id)?;
+
+        // Order by (timestamp, id) so cursors stay valid when versions are pruned or imported
+        let mut versions: Vec<&VersionMetadata> = index.versions.iter().collect();
+        versions.sort_by(|a, b| (b.timestamp, &b.id).cmp(&(a.timestamp, &a.id)));
+
+        let mut content_matches: HashMap<String, bool> = HashMap::new();
+        let mut matches = |version: &VersionMetadata| -> Result<bool> {
+            let matches = query.since.map_or(true, |since| version.timestamp >= since)
+                && query.until.map_or(true, |until| version.timestamp <= until)
+                && query.auto_save.map_or(true, |auto| version.is_auto_save == auto)
+                && query.tag.as_ref().map_or(true, |tag| version.tags.contains(tag))
+                && note.as_ref().map_or(true, |needle| {
+                    version.note.as_ref().is_some_and(|n| n.to_lowercase().contains(needle))
+                });
+            let Some(ref needle) = query.content else {
+                return Ok(matches);
+            };
+            if !matches {
+                return Ok(false);
+            }
+
+            // Versions sharing a blob share the answer
+            if let Some(found) = content_matches.get(&version.checksum) {
+                return Ok(*found);
+            }
+            let found = self.read_content(software_id, version)?.contains(needle.as_str());
+            content_matches.insert(version.checksum.clone(), found);
+            Ok(found)
+        };
+
+        let mut page = Vec::new();
+        let remaining = versions
+            .into_iter()
+            .filter(|v| after.as_ref().map_or(true, |(ts, id)| (v.timestamp, &v.id) < (*ts, id)));
+        let mut has_more = false;
+        for version in remaining {
+            if !matches(version)? {
+                continue;
+            }
+            if page.len() == limit {
+                has_more = true;
+                break;
+            }
+            page.push(VersionSummary::from(version));
+        }
+
+        // Only hand out a cursor when the next page has something to show
+        let next_cursor = match (has_more, page.last()) {
+            (true, Some(last)) => Some(format_cursor(last)),
+            _ => None,
+        };
+
+        Ok(HistoryPage { versions: page, next_cursor })
+    }
+
+    pub(super) fn read_content(&self, software_id: &str, metadata: &VersionMetadata) -> Result<String> {
+        match metadata.layout {
+            VersionLayout::Blob => self.blobs(software_id)?.get(&metadata.checksum),
+            VersionLayout::Inline => {
+                let path = self.get_record_path(software_id, &metadata.file_name)?;
+                Ok(Self::read_inline_content(&path)?.0)
+            }
+        }
+    }
+}
+
+pub(super) fn format_cursor(version: &VersionSummary) -> String {
+    format!("{}|{}", version.timestamp.to_rfc3339(), version.id)
+}
+
+pub(super) fn parse_cursor(cursor: &str) -> Result<(DateTime<Utc>, String)> {
+    let invalid = || anyhow!("Invalid history cursor: {:?}", cursor);
+    let (timestamp, id) = cursor.split_once('|').ok_or_else(invalid)?;
+    let timestamp = DateTime::parse_from_rfc3339(timestamp).map_err(|_| invalid())?;
+    Ok((timestamp.with_timezone(&Utc), id.to_string()))
+}
@@ -0,0 +1,148 @@
+use anyhow::Result;
+
+use super::StorageError;
+
+const MAX_SOFTWARE_ID_LEN: usize = 64;
+const MAX_TAG_LEN: usize = 64;
+const MAX_LABEL_LEN: usize = 200;
+
+// Software ids become directory names, so only allow a conservative charset:
+// ASCII letters, digits, '-' and '_', starting with a letter or digit.
+// Excluding '.', '/' and '\' rules out `..`, absolute paths and separators.
+pub fn validate_software_id(software_id: &str) -> Result<()> {
+    let valid = !software_id.is_empty()
+        && software_id.len() <= MAX_SOFTWARE_ID_LEN
+        && software_id.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && software_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(invalid("software id", software_id))
+    }
+}
+
+// Version ids are generated as UUIDs and are used in file names
+pub fn validate_version_id(version_id: &str) -> Result<()> {
+    match uuid::Uuid::parse_str(version_id) {
+        Ok(uuid) if uuid.hyphenated().to_string() == version_id.to_ascii_lowercase() => Ok(()),
+        _ => Err(invalid("version id", version_id)),
+    }
+}
+
+// Record file names stored in the index must be `<version id>.json`
+pub fn validate_record_file_name(file_name: &str) -> Result<()> {
+    match file_name.strip_suffix(".json") {
+        Some(stem) if validate_version_id(stem).is_ok() => Ok(()),
+        _ => Err(invalid("version file name", file_name)),
+    }
+}
+
+// Blob digests are lowercase hex SHA-256 strings
+pub fn validate_digest(digest: &str) -> Result<()> {
+    let valid = digest.len() == 64
+        && digest.chars().all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(invalid("blob digest", digest))
+    }
+}
+
+// Tags are stable handles like `known-good` or `before-upgrade-1.2`
+pub fn validate_tag(tag: &str) -> Result<()> {
+    let valid = !tag.is_empty()
+        && tag.len() <= MAX_TAG_LEN
+        && tag.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(invalid("tag", tag))
+    }
+}
+
+// Labels are free text, but must be non-empty, single-line and reasonably short
+pub fn validate_label(label: &str) -> Result<()> {
+    let valid = !label.trim().is_empty()
+        && label.chars().count() <= MAX_LABEL_LEN
+        && !label.contains(['\n', '\r']);
+
+    if valid {
+        Ok(())
+    } else {
+        Err(invalid("label", label))
+    }
+}
+
+fn invalid(kind: &str, value: &str) -> anyhow::Error {
+    StorageError::InvalidId {
+        kind: kind.to_string(),
+        value: value.to_string(),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_software_id_rejects_traversal() {
+        for id in ["zsh", "oh-my-zsh", "vscode_insiders", "Git2"] {
+            assert!(validate_software_id(id).is_ok(), "{}", id);
+        }
+
+        for id in [
+            "", "..", "../..", "../../..", ".", "a/b", "a\\b", "/etc", "C:", "C:\\Windows",
+            "-rf", "zsh/../../etc", "zsh\0", " zsh", "~", "a.b",
+        ] {
+            assert!(validate_software_id(id).is_err(), "{:?}", id);
+        }
+
+        assert!(validate_software_id(&"a".repeat(MAX_SOFTWARE_ID_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_version_id_must_be_uuid() {
+        assert!(validate_version_id("67e55044-10b1-426f-9247-bb680e5fe0c8").is_ok());
+
+        for id in [
+            "", "..", "../index", "index", "67e55044-10b1-426f-9247-bb680e5fe0c8/../x",
+            "67e5504410b1426f9247bb680e5fe0c8", "{67e55044-10b1-426f-9247-bb680e5fe0c8}",
+        ] {
+            assert!(validate_version_id(id).is_err(), "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn test_record_file_name_and_digest() {
+        assert!(validate_record_file_name("67e55044-10b1-426f-9247-bb680e5fe0c8.json").is_ok());
+        assert!(validate_record_file_name("../index.json").is_err());
+        assert!(validate_record_file_name("index.json").is_err());
+
+        assert!(validate_digest(&"ab".repeat(32)).is_ok());
+        assert!(validate_digest(&"AB".repeat(32)).is_err());
+        assert!(validate_digest("../../secret").is_err());
+    }
+
+    #[test]
+    fn test_tags_and_labels() {
+        for tag in ["known-good", "before-upgrade", "v1.2_rc"] {
+            assert!(validate_tag(tag).is_ok(), "{}", tag);
+        }
+        for tag in ["", "-x", "known good", "a/b", ".hidden"] {
+            assert!(validate_tag(tag).is_err(), "{:?}", tag);
+        }
+
+        assert!(validate_label("Works with plugin 3.x").is_ok());
+        assert!(validate_label("  ").is_err());
+        assert!(validate_label("two\nlines").is_err());
+    }
+}
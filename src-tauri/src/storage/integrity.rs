@@ -0,0 +1,142 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use super::{VersionIndex, VersionLayout, VersionStorage};
+
+// Integrity findings for one software's version history
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SoftwareVerification {
+    pub software_id: String,
+    pub version_count: usize,
+    // Blobs that no version in the index references
+    pub orphan_blobs: Vec<String>,
+    // Version records that are not listed in the index
+    pub orphan_records: Vec<String>,
+    // Version ids whose record file is missing
+    pub missing_records: Vec<String>,
+    // Version ids whose content blob is missing
+    pub missing_blobs: Vec<String>,
+    // Version ids whose stored content does not match the recorded checksum
+    pub checksum_mismatches: Vec<String>,
+}
+
+impl SoftwareVerification {
+    pub fn is_healthy(&self) -> bool {
+        self.orphan_blobs.is_empty()
+            && self.orphan_records.is_empty()
+            && self.missing_records.is_empty()
+            && self.missing_blobs.is_empty()
+            && self.checksum_mismatches.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageVerification {
+    pub checked_at: DateTime<Utc>,
+    pub healthy: bool,
+    pub software: Vec<SoftwareVerification>,
+}
+
+impl VersionStorage {
+    // Check every software's index against its records and blobs
+    pub fn verify_storage(&self) -> Result<StorageVerification> {
+        let _lock = self.lock()?;
+        let mut software = Vec::new();
+
+        for software_id in self.software_ids()? {
+            software.push(self.check_software(&software_id)?);
+        }
+
+        Ok(StorageVerification {
+            checked_at: Utc::now(),
+            healthy: software.iter().all(|s| s.is_healthy()),
+            software,
+        })
+    }
+
+    // Check one software's index against its records and blobs
+    fn check_software(&self, software_id: &str) -> Result<SoftwareVerification> {
+        let index = self.load_index(software_id)?;
+        let blobs = self.blobs(software_id)?;
+
+        let mut report = SoftwareVerification {
+            software_id: software_id.to_string(),
+            version_count: index.versions.len(),
+            ..Default::default()
+        };
+
+        for metadata in index.versions.iter().chain(&index.squashed) {
+            let record_path = self.get_record_path(software_id, &metadata.file_name)?;
+            if !record_path.exists() && metadata.layout == VersionLayout::Inline {
+                // Inline versions keep their content in the record itself
+                report.missing_blobs.push(metadata.id.clone());
+                continue;
+            }
+            if !record_path.exists() {
+                report.missing_records.push(metadata.id.clone());
+            }
+
+            let content = match metadata.layout {
+                VersionLayout::Blob if !blobs.exists(&metadata.checksum) => {
+                    report.missing_blobs.push(metadata.id.clone());
+                    continue;
+                }
+                // Locked history cannot be decrypted to verify its checksum
+                VersionLayout::Blob if blobs.is_locked() => continue,
+                VersionLayout::Blob => blobs.get(&metadata.checksum).ok(),
+                VersionLayout::Inline => Self::read_inline_content(&record_path)
+                    .ok()
+                    .map(|(content, _)| content),
+            };
+
+            let matches = match content {
+                Some(content) => {
+                    self.content_checksum(software_id, metadata.checksum_algorithm, &content)?.as_ref()
+                        == Some(&metadata.checksum)
+                }
+                None => false,
+            };
+            if !matches {
+                report.checksum_mismatches.push(metadata.id.clone());
+            }
+        }
+
+        (report.orphan_records, report.orphan_blobs) = self.find_orphans(software_id, &index)?;
+
+        Ok(report)
+    }
+
+    // Record file names and blob digests that no entry of the index refers to
+    pub(super) fn find_orphans(&self, software_id: &str, index: &VersionIndex) -> Result<(Vec<String>, Vec<String>)> {
+        let indexed_files: HashSet<&str> = index.versions
+            .iter()
+            .chain(&index.squashed)
+            .map(|v| v.file_name.as_str())
+            .collect();
+        let referenced_blobs: HashSet<&str> = index.versions
+            .iter()
+            .chain(&index.squashed)
+            .filter(|v| v.layout == VersionLayout::Blob)
+            .map(|v| v.checksum.as_str())
+            .collect();
+
+        let mut orphan_records = Vec::new();
+        for path in self.list_record_paths(software_id)? {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            if !indexed_files.contains(file_name.as_ref()) {
+                orphan_records.push(file_name.to_string());
+            }
+        }
+
+        let mut orphan_blobs = Vec::new();
+        for digest in self.blobs(software_id)?.list()? {
+            if !referenced_blobs.contains(digest.as_str()) {
+                orphan_blobs.push(digest);
+            }
+        }
+
+        Ok((orphan_records, orphan_blobs))
+    }
+}
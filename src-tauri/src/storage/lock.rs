@@ -0,0 +1,73 @@
+use anyhow::Result;
+use fs4::fs_std::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::StorageError;
+
+// How long to wait for another process before reporting contention
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(25);
+
+// Serializes read-modify-write cycles on a storage location, both between
+// threads of this process (mutex) and between processes (advisory file lock)
+pub struct StorageLock {
+    path: PathBuf,
+    mutex: Mutex<()>,
+    timeout: Duration,
+}
+
+// Holds the lock until dropped; closing the lock file releases the advisory lock
+pub struct StorageLockGuard<'a> {
+    _file: File,
+    _guard: MutexGuard<'a, ()>,
+}
+
+impl StorageLock {
+    pub fn new(path: PathBuf) -> Self {
+        Self::with_timeout(path, LOCK_TIMEOUT)
+    }
+
+    // Use a different wait for contention, e.g. a short one in tests
+    pub fn with_timeout(path: PathBuf, timeout: Duration) -> Self {
+        Self {
+            path,
+            mutex: Mutex::new(()),
+            timeout,
+        }
+    }
+
+    // Acquire the lock, failing with StorageError::Locked if it stays contended
+    pub fn acquire(&self) -> Result<StorageLockGuard<'_>> {
+        // A panic while holding the lock leaves no partial state on disk thanks to atomic writes
+        let guard = self.mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&self.path)?;
+
+        let started = Instant::now();
+        while !file.try_lock_exclusive()? {
+            if started.elapsed() >= self.timeout {
+                return Err(StorageError::Locked {
+                    path: self.path.to_string_lossy().to_string(),
+                }
+                .into());
+            }
+            thread::sleep(LOCK_RETRY_INTERVAL);
+        }
+
+        Ok(StorageLockGuard {
+            _file: file,
+            _guard: guard,
+        })
+    }
+}
@@ -0,0 +1,309 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{VersionIndex, VersionLayout, VersionStorage};
+
+// Disk usage of one software's version history
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SoftwareStorageStats {
+    pub software_id: String,
+    // Everything under the software's directory
+    pub total_bytes: u64,
+    pub version_count: usize,
+    pub auto_save_count: usize,
+    pub manual_count: usize,
+    // Auto-saves hidden by squashing, still on disk until pruned
+    pub squashed_count: usize,
+    pub oldest: Option<DateTime<Utc>>,
+    pub newest: Option<DateTime<Utc>>,
+    pub blob_count: usize,
+    pub blob_bytes: u64,
+    // Bytes not written because versions with identical content share one blob
+    pub dedup_savings_bytes: u64,
+    pub orphan_blobs: usize,
+    pub orphan_records: usize,
+    // Leftovers of interrupted writes
+    pub stray_files: usize,
+    // Bytes taken by orphans and stray files, reclaimable by compaction
+    pub orphan_bytes: u64,
+    pub encrypted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageStats {
+    pub collected_at: DateTime<Utc>,
+    pub base_path: String,
+    pub total_bytes: u64,
+    pub version_count: usize,
+    pub software: Vec<SoftwareStorageStats>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SoftwareCompaction {
+    pub software_id: String,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    // Versions missing from the index that were found among the records
+    pub versions_recovered: usize,
+    // Versions whose lost record was rewritten from the index
+    pub records_restored: usize,
+    pub orphan_blobs_removed: usize,
+    pub orphan_secrets_removed: usize,
+    pub stray_files_removed: usize,
+    pub blobs_recompressed: usize,
+    // Records that could not be read; left in place for inspection
+    pub unreadable_records: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactionReport {
+    pub compacted_at: DateTime<Utc>,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub software: Vec<SoftwareCompaction>,
+}
+
+impl VersionStorage {
+    // Sizes and counts of the stored history, without reading any content
+    pub fn get_storage_stats(&self) -> Result<StorageStats> {
+        let _lock = self.lock()?;
+        let mut software = Vec::new();
+
+        for software_id in self.software_ids()? {
+            software.push(self.software_stats(&software_id)?);
+        }
+
+        Ok(StorageStats {
+            collected_at: Utc::now(),
+            base_path: self.base_path().display().to_string(),
+            total_bytes: dir_size(self.base_path())?,
+            version_count: software.iter().map(|s| s.version_count).sum(),
+            software,
+        })
+    }
+
+    // Rebuild every index from its records, then remove orphans and leftovers of interrupted
+    // writes and recompress blobs at the best level
+    pub fn compact_storage(&self) -> Result<CompactionReport> {
+        let _lock = self.lock()?;
+        let bytes_before = dir_size(self.base_path())?;
+        let mut software = Vec::new();
+
+        for software_id in self.software_ids()? {
+            software.push(self.compact_software(&software_id)?);
+        }
+
+        let bytes_after = dir_size(self.base_path())?;
+        log::info!("Compacted version storage from {} to {} bytes", bytes_before, bytes_after);
+
+        Ok(CompactionReport {
+            compacted_at: Utc::now(),
+            bytes_before,
+            bytes_after,
+            software,
+        })
+    }
+
+    fn software_stats(&self, software_id: &str) -> Result<SoftwareStorageStats> {
+        let software_path = self.get_software_path(software_id)?;
+        let index = self.load_index(software_id)?;
+        let blobs = self.blobs(software_id)?;
+
+        let mut blob_sizes = HashMap::new();
+        for digest in blobs.list()? {
+            let size = fs::metadata(blobs.blob_path(&digest)?).map(|m| m.len()).unwrap_or(0);
+            blob_sizes.insert(digest, size);
+        }
+
+        // Every reference to a blob beyond the first is content that was not stored again
+        let mut referenced = HashSet::new();
+        let mut dedup_savings_bytes = 0;
+        for version in index.versions.iter().chain(&index.squashed) {
+            if version.layout != VersionLayout::Blob {
+                continue;
+            }
+            let size = blob_sizes.get(&version.checksum).copied().unwrap_or(0);
+            if !referenced.insert(version.checksum.as_str()) {
+                dedup_savings_bytes += size;
+            }
+        }
+
+        let (orphan_records, orphan_blobs) = self.find_orphans(software_id, &index)?;
+        let stray = stray_files(&software_path)?;
+        let mut orphan_bytes: u64 = orphan_blobs.iter().filter_map(|d| blob_sizes.get(d)).sum();
+        for name in &orphan_records {
+            orphan_bytes += fs::metadata(software_path.join(name)).map(|m| m.len()).unwrap_or(0);
+        }
+        for path in &stray {
+            orphan_bytes += fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        }
+
+        let auto_save_count = index.versions.iter().filter(|v| v.is_auto_save).count();
+        Ok(SoftwareStorageStats {
+            software_id: software_id.to_string(),
+            total_bytes: dir_size(&software_path)?,
+            version_count: index.versions.len(),
+            auto_save_count,
+            manual_count: index.versions.len() - auto_save_count,
+            squashed_count: index.squashed.len(),
+            oldest: index.versions.first().map(|v| v.timestamp),
+            newest: index.versions.last().map(|v| v.timestamp),
+            blob_count: blob_sizes.len(),
+            blob_bytes: blob_sizes.values().sum(),
+            dedup_savings_bytes,
+            orphan_blobs: orphan_blobs.len(),
+            orphan_records: orphan_records.len(),
+            stray_files: stray.len(),
+            orphan_bytes,
+            encrypted: self.encryption_status(software_id)?.encrypted,
+        })
+    }
+
+    fn compact_software(&self, software_id: &str) -> Result<SoftwareCompaction> {
+        let software_path = self.get_software_path(software_id)?;
+        let mut report = SoftwareCompaction {
+            software_id: software_id.to_string(),
+            bytes_before: dir_size(&software_path)?,
+            ..Default::default()
+        };
+
+        // Loading first commits or discards a pending index, so what remains is garbage
+        let previous = self.load_index(software_id)?;
+        for path in stray_files(&software_path)? {
+            fs::remove_file(path)?;
+            report.stray_files_removed += 1;
+        }
+
+        // Records are written before the index, so they are the more complete source. Versions
+        // whose record was lost get it back from the old index instead of being dropped.
+        let rebuilt = self.reconstruct_index(software_id)?;
+        let known: HashSet<String> = rebuilt.versions
+            .iter()
+            .chain(&rebuilt.squashed)
+            .map(|v| v.id.clone())
+            .collect();
+        let mut index = VersionIndex {
+            max_versions: previous.max_versions,
+            retention: previous.retention.clone(),
+            secret_policy: previous.secret_policy,
+            ..rebuilt
+        };
+        index.imported_commits.extend(previous.imported_commits.iter().cloned());
+        for version in previous.versions.iter().chain(&previous.squashed) {
+            if known.contains(&version.id) {
+                continue;
+            }
+            self.write_record(software_id, version)?;
+            report.records_restored += 1;
+            match version.squashed_into {
+                Some(_) => index.squashed.push(version.clone()),
+                None => index.versions.push(version.clone()),
+            }
+        }
+        index.versions.sort_by_key(|v| v.timestamp);
+        index.squashed.sort_by_key(|v| v.timestamp);
+        self.save_index(software_id, &index)?;
+
+        let count = |index: &VersionIndex| index.versions.len() + index.squashed.len();
+        report.versions_recovered = (count(&index) - report.records_restored).saturating_sub(count(&previous));
+
+        let (unreadable_records, orphan_blobs) = self.find_orphans(software_id, &index)?;
+        report.unreadable_records = unreadable_records;
+        let blobs = self.blobs(software_id)?;
+        for digest in &orphan_blobs {
+            blobs.remove(digest)?;
+        }
+        report.orphan_blobs_removed = orphan_blobs.len();
+
+        let ids: HashSet<&str> = index.versions.iter().chain(&index.squashed).map(|v| v.id.as_str()).collect();
+        for (version_id, path) in self.sealed_secret_files(software_id)? {
+            if !ids.contains(version_id.as_str()) {
+                fs::remove_file(path)?;
+                report.orphan_secrets_removed += 1;
+            }
+        }
+
+        for digest in blobs.list()? {
+            if blobs.recompress(&digest)? > 0 {
+                report.blobs_recompressed += 1;
+            }
+        }
+
+        report.bytes_after = dir_size(&software_path)?;
+        Ok(report)
+    }
+}
+
+fn dir_size(path: &Path) -> Result<u64> {
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        total += match metadata.is_dir() {
+            true => dir_size(&entry.path())?,
+            false => metadata.len(),
+        };
+    }
+    Ok(total)
+}
+
+// Pending copies left behind by writes that never completed
+fn stray_files(path: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !path.exists() {
+        return Ok(files);
+    }
+
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(stray_files(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "pending") {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compaction_removes_orphans_and_repairs_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = VersionStorage::with_base_path(dir.path().to_path_buf()).unwrap();
+        storage.set_max_versions("zsh", 50).unwrap();
+
+        let first = storage.save_version("zsh", "a\n", None, false).unwrap();
+        storage.save_version("zsh", "b\n", None, true).unwrap();
+        storage.save_version("zsh", "a\n", None, false).unwrap();
+
+        let software_path = dir.path().join("zsh");
+        storage.blobs("zsh").unwrap().put("orphan\n").unwrap();
+        fs::write(software_path.join("blobs").join("x.gz.pending"), "partial").unwrap();
+        fs::remove_file(software_path.join(format!("{}.json", first.id))).unwrap();
+
+        let stats = storage.get_storage_stats().unwrap();
+        let zsh = &stats.software[0];
+        assert_eq!((zsh.version_count, zsh.auto_save_count, zsh.manual_count), (3, 1, 2));
+        assert_eq!((zsh.blob_count, zsh.orphan_blobs, zsh.stray_files), (3, 1, 1));
+        assert!(zsh.dedup_savings_bytes > 0);
+        assert_eq!(zsh.oldest, Some(first.timestamp));
+
+        let report = storage.compact_storage().unwrap();
+        let zsh = &report.software[0];
+        assert_eq!((zsh.orphan_blobs_removed, zsh.stray_files_removed, zsh.records_restored), (1, 1, 1));
+        assert_eq!(storage.get_max_versions("zsh").unwrap(), 50);
+        assert_eq!(storage.get_history("zsh", None).unwrap().len(), 3);
+        assert!(storage.verify_storage().unwrap().healthy);
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::store::{PreferenceStore, VersionStore};
+
+// Where version history and preferences are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackend {
+    // JSON documents and blobs under the app data directory
+    Files,
+    // A single embedded SQLite database
+    Sqlite,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SoftwareMigration {
+    pub software_id: String,
+    pub copied: usize,
+    // Versions the target already had
+    pub skipped: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationReport {
+    pub from: StorageBackend,
+    pub to: StorageBackend,
+    pub software: Vec<SoftwareMigration>,
+    pub preferences_copied: usize,
+}
+
+pub struct StorageMigrator;
+
+impl StorageMigrator {
+    // Copy every version, keeping ids and timestamps, plus per-software settings and preferences.
+    // The global retention policy stays in the app settings. The source
+    // is left untouched and versions the target already has are skipped, so re-running is safe.
+    pub fn migrate(
+        from: (StorageBackend, &dyn VersionStore, &dyn PreferenceStore),
+        to: (StorageBackend, &dyn VersionStore, &dyn PreferenceStore),
+    ) -> Result<MigrationReport> {
+        let (from_backend, from_versions, from_preferences) = from;
+        let (to_backend, to_versions, to_preferences) = to;
+
+        let mut software = Vec::new();
+        for software_id in from_versions.software_ids()? {
+            let dump = from_versions.dump_software(&software_id)?;
+            let copied = to_versions.load_dump(&dump)?;
+            software.push(SoftwareMigration {
+                software_id,
+                copied,
+                skipped: dump.versions.len() - copied,
+            });
+        }
+
+        let preferences = from_preferences.list_preferences()?;
+        let preferences_copied = preferences.len();
+        for preferences in preferences {
+            to_preferences.save_preferences(preferences)?;
+        }
+
+        log::info!(
+            "Migrated {} versions and {} preferences from {:?} to {:?}",
+            software.iter().map(|s| s.copied).sum::<usize>(),
+            preferences_copied,
+            from_backend,
+            to_backend
+        );
+
+        Ok(MigrationReport {
+            from: from_backend,
+            to: to_backend,
+            software,
+            preferences_copied,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{SqliteStore, VersionStorage};
+
+    #[test]
+    fn test_round_trip_between_files_and_sqlite() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = VersionStorage::with_base_path(dir.path().join("versions")).unwrap();
+        let preferences = crate::storage::PreferencesStorage::with_file_path(dir.path().join("preferences.json"));
+        let sqlite = SqliteStore::open(&dir.path().join("test.db")).unwrap();
+
+        files.set_secret_policy("zsh", crate::storage::SecretPolicy::Redact).unwrap();
+        let first = files.save_version("zsh", "a\n", Some("Initial".into()), false).unwrap();
+        files.save_version("zsh", "b\n", None, true).unwrap();
+        files.add_tag("zsh", &first.id, "known-good").unwrap();
+        preferences.save_preferences(crate::storage::SoftwarePreferences {
+            software_id: "zsh".into(),
+            ..Default::default()
+        }).unwrap();
+
+        let report = StorageMigrator::migrate(
+            (StorageBackend::Files, &files, &preferences),
+            (StorageBackend::Sqlite, &sqlite, &sqlite),
+        ).unwrap();
+        assert_eq!(report.software[0].copied, 2);
+        assert_eq!(report.preferences_copied, 1);
+        let tagged = VersionStore::get_tagged_version(&sqlite, "zsh", "known-good").unwrap();
+        assert_eq!(tagged.unwrap().id, first.id);
+        let dump = VersionStore::dump_software(&sqlite, "zsh").unwrap();
+        assert_eq!(dump.secret_policy, Some(crate::storage::SecretPolicy::Redact));
+
+        // Re-running copies nothing, and copying back to a fresh file store restores the history
+        let again = StorageMigrator::migrate(
+            (StorageBackend::Files, &files, &preferences),
+            (StorageBackend::Sqlite, &sqlite, &sqlite),
+        ).unwrap();
+        assert_eq!(again.software[0].skipped, 2);
+
+        let restored = VersionStorage::with_base_path(dir.path().join("restored")).unwrap();
+        StorageMigrator::migrate(
+            (StorageBackend::Sqlite, &sqlite, &sqlite),
+            (StorageBackend::Files, &restored, &preferences),
+        ).unwrap();
+        let history = restored.get_history("zsh", None).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].timestamp, first.timestamp);
+        assert!(restored.verify_storage().unwrap().healthy);
+    }
+}
@@ -1,5 +1,39 @@
+pub mod atomic;
+pub mod blob_store;
+pub mod encryption;
+pub mod error;
+pub mod history;
+pub mod ids;
+pub mod integrity;
+pub mod lock;
+pub mod maintenance;
+pub mod migrate;
+pub mod retention;
+pub mod schema;
+pub mod secrets;
+pub mod settings;
+pub mod sqlite;
+pub mod squash;
+pub mod store;
+pub mod tags;
 pub mod version_storage;
 pub mod preferences;
 
+pub use blob_store::*;
+pub use encryption::*;
+pub use error::*;
+pub use history::*;
+pub use ids::*;
+pub use integrity::*;
+pub use lock::*;
+pub use maintenance::*;
+pub use migrate::*;
+pub use retention::*;
+pub use schema::*;
+pub use secrets::*;
+pub use settings::*;
+pub use sqlite::*;
+pub use squash::*;
+pub use store::*;
 pub use version_storage::*;
-pub use preferences::*;
\ No newline at end of file
+pub use preferences::*;
@@ -1,9 +1,13 @@
 use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 use tauri::Manager;
 
+use super::schema::{read_document, write_document, DocumentKind};
+use super::{AppSettings, EffectivePreferences, PreferenceStore, StorageLock};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SoftwarePreferences {
     pub software_id: String,
@@ -27,8 +31,69 @@ impl Default for SoftwarePreferences {
     }
 }
 
+// How often scheduled backups run on their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupInterval {
+    Hourly,
+    Daily,
+}
+
+impl BackupInterval {
+    pub fn period(self) -> Duration {
+        match self {
+            BackupInterval::Hourly => Duration::hours(1),
+            BackupInterval::Daily => Duration::days(1),
+        }
+    }
+}
+
+// When automatic backups are taken; software opts out through `auto_backup`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupSchedule {
+    // `None` disables periodic backups
+    pub interval: Option<BackupInterval>,
+    pub on_start: bool,
+    // Snapshot the file on disk before the app overwrites it
+    pub before_apply: bool,
+    // When the interval last fired, kept so restarts do not reset the cadence
+    #[serde(default)]
+    pub last_run: Option<DateTime<Utc>>,
+}
+
+impl Default for BackupSchedule {
+    fn default() -> Self {
+        Self {
+            interval: Some(BackupInterval::Daily),
+            on_start: true,
+            before_apply: true,
+            last_run: None,
+        }
+    }
+}
+
+impl BackupSchedule {
+    // When the next periodic backup is due, if any
+    pub fn next_run(&self) -> Option<DateTime<Utc>> {
+        let interval = self.interval?;
+        Some(self.last_run.map_or_else(Utc::now, |last| last + interval.period()))
+    }
+}
+
+// On-disk layout of the preferences file
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PreferencesFile {
+    preferences: Vec<SoftwarePreferences>,
+    #[serde(default)]
+    backup_schedule: BackupSchedule,
+    // `None` until the settings are first saved
+    #[serde(default)]
+    settings: Option<AppSettings>,
+}
+
 pub struct PreferencesStorage {
     file_path: PathBuf,
+    lock: StorageLock,
 }
 
 impl PreferencesStorage {
@@ -38,47 +103,119 @@ impl PreferencesStorage {
             .path()
             .app_data_dir()?;
         
-        let file_path = app_dir.join("preferences.json");
-        
         // Ensure directory exists
         fs::create_dir_all(&app_dir)?;
         
-        Ok(Self { file_path })
+        Ok(Self::with_file_path(app_dir.join("preferences.json")))
+    }
+    
+    // Keep preferences in the given file, locked through a sibling `.lock` file
+    pub fn with_file_path(file_path: PathBuf) -> Self {
+        Self {
+            lock: StorageLock::new(file_path.with_extension("json.lock")),
+            file_path,
+        }
+    }
+    
+    // Get the automatic backup schedule
+    pub fn get_backup_schedule(&self) -> Result<BackupSchedule> {
+        let _lock = self.lock.acquire()?;
+        Ok(self.load_file()?.backup_schedule)
+    }
+    
+    // Replace the automatic backup schedule
+    pub fn save_backup_schedule(&self, schedule: BackupSchedule) -> Result<()> {
+        let _lock = self.lock.acquire()?;
+        let mut file = self.load_file()?;
+        file.backup_schedule = schedule;
+        self.save_file(&file)
+    }
+    
+    // Get the app-wide settings, or the defaults if none were saved
+    pub fn get_settings(&self) -> Result<AppSettings> {
+        Ok(self.stored_settings()?.unwrap_or_default())
+    }
+    
+    // Get the app-wide settings only if they were saved
+    pub fn stored_settings(&self) -> Result<Option<AppSettings>> {
+        let _lock = self.lock.acquire()?;
+        Ok(self.load_file()?.settings)
+    }
+    
+    // Validate and replace the app-wide settings, returning the previous ones
+    pub fn save_settings(&self, settings: AppSettings) -> Result<AppSettings> {
+        settings.validate()?;
+        
+        let _lock = self.lock.acquire()?;
+        let mut file = self.load_file()?;
+        let previous = file.settings.replace(settings).unwrap_or_default();
+        self.save_file(&file)?;
+        Ok(previous)
     }
     
-    // Load all preferences
-    fn load_all(&self) -> Result<Vec<SoftwarePreferences>> {
+    // A software's preferences, noting whether they are inherited from the settings
+    pub fn resolve_preferences(&self, software_id: &str) -> Result<EffectivePreferences> {
+        let _lock = self.lock.acquire()?;
+        let file = self.load_file()?;
+        
+        Ok(match file.preferences.into_iter().find(|p| p.software_id == software_id) {
+            Some(preferences) => EffectivePreferences {
+                preferences,
+                inherited: false,
+            },
+            None => EffectivePreferences {
+                preferences: file.settings.unwrap_or_default().software_defaults.for_software(software_id),
+                inherited: true,
+            },
+        })
+    }
+    
+    // Drop a software's own preferences so it inherits the settings again.
+    // Returns whether it had any.
+    pub fn reset_preferences(&self, software_id: &str) -> Result<bool> {
+        let _lock = self.lock.acquire()?;
+        let mut file = self.load_file()?;
+        let before = file.preferences.len();
+        file.preferences.retain(|p| p.software_id != software_id);
+        
+        if file.preferences.len() == before {
+            return Ok(false);
+        }
+        self.save_file(&file)?;
+        Ok(true)
+    }
+    
+    // Load the whole preferences file
+    fn load_file(&self) -> Result<PreferencesFile> {
         if self.file_path.exists() {
-            let content = fs::read_to_string(&self.file_path)?;
-            Ok(serde_json::from_str(&content)?)
+            read_document(DocumentKind::Preferences, &self.file_path)
         } else {
-            Ok(Vec::new())
+            Ok(PreferencesFile::default())
         }
     }
     
-    // Save all preferences
-    fn save_all(&self, preferences: &[SoftwarePreferences]) -> Result<()> {
-        let content = serde_json::to_string_pretty(preferences)?;
-        fs::write(&self.file_path, content)?;
-        Ok(())
+    fn save_file(&self, file: &PreferencesFile) -> Result<()> {
+        write_document(DocumentKind::Preferences, &self.file_path, file)
     }
-    
+}
+
+impl PreferenceStore for PreferencesStorage {
     // Get preferences for a software
-    pub fn get_preferences(&self, software_id: &str) -> Result<SoftwarePreferences> {
-        let all = self.load_all()?;
-        
-        Ok(all
-            .into_iter()
-            .find(|p| p.software_id == software_id)
-            .unwrap_or_else(|| SoftwarePreferences {
-                software_id: software_id.to_string(),
-                ..Default::default()
-            }))
+    fn get_preferences(&self, software_id: &str) -> Result<SoftwarePreferences> {
+        Ok(self.resolve_preferences(software_id)?.preferences)
+    }
+    
+    // Every software's saved preferences
+    fn list_preferences(&self) -> Result<Vec<SoftwarePreferences>> {
+        let _lock = self.lock.acquire()?;
+        Ok(self.load_file()?.preferences)
     }
     
     // Save preferences for a software
-    pub fn save_preferences(&self, preferences: SoftwarePreferences) -> Result<()> {
-        let mut all = self.load_all()?;
+    fn save_preferences(&self, preferences: SoftwarePreferences) -> Result<()> {
+        let _lock = self.lock.acquire()?;
+        let mut file = self.load_file()?;
+        let all = &mut file.preferences;
         
         // Update or add preferences
         if let Some(pos) = all.iter().position(|p| p.software_id == preferences.software_id) {
@@ -87,7 +224,7 @@ impl PreferencesStorage {
             all.push(preferences);
         }
         
-        self.save_all(&all)?;
+        self.save_file(&file)?;
         Ok(())
     }
 }
\ No newline at end of file
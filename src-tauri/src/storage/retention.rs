@@ -0,0 +1,502 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::PoisonError;
+
+use super::schema::{read_document, DocumentKind};
+use super::squash::DEFAULT_SQUASH_WINDOW_MINUTES;
+use super::{VersionIndex, VersionLayout, VersionMetadata, VersionStorage};
+
+pub(super) const GLOBAL_POLICY_FILE: &str = "retention.json";
+
+// How long squashed auto-saves stay restorable when the policy has no `keep_all_hours` tier
+pub const DEFAULT_SQUASH_RESTORE_HOURS: u32 = 24;
+
+// How long versions are kept, thinning older history into coarser buckets.
+// Each tier is measured from now; a `None` tier is skipped. Without any tier, auto-saves
+// are only removed by the count and byte caps, which is the default until the user opts in.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    // Keep every auto-save younger than this
+    pub keep_all_hours: Option<u32>,
+    // Then keep the newest auto-save per hour up to this age
+    pub hourly_days: Option<u32>,
+    // Then the newest per day
+    pub daily_days: Option<u32>,
+    // Then the newest per ISO week; older auto-saves are dropped
+    pub weekly_weeks: Option<u32>,
+    // Cap on the stored bytes of a software's history, oldest unpinned versions go first
+    pub max_bytes: Option<u64>,
+    // Expire manual saves after this many days; `None` keeps them forever
+    pub manual_expiry_days: Option<u32>,
+    // Squash finished runs of auto-saves no further apart than this; `None` disables it.
+    // Squashed auto-saves can be restored until they are older than `keep_all_hours`,
+    // or `DEFAULT_SQUASH_RESTORE_HOURS` without that tier.
+    #[serde(default)]
+    pub squash_window_minutes: Option<u32>,
+}
+
+impl RetentionPolicy {
+    // Suggested tiers to opt in to: everything for a day, then hourly for a week,
+    // daily for a month and weekly for a year, squashing runs of auto-saves
+    pub fn tiered() -> Self {
+        Self {
+            keep_all_hours: Some(24),
+            hourly_days: Some(7),
+            daily_days: Some(30),
+            weekly_weeks: Some(52),
+            squash_window_minutes: Some(DEFAULT_SQUASH_WINDOW_MINUTES),
+            ..Self::default()
+        }
+    }
+
+    // Whether any time tier is set
+    fn has_tiers(&self) -> bool {
+        [self.keep_all_hours, self.hourly_days, self.daily_days, self.weekly_weeks]
+            .iter()
+            .any(Option::is_some)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let spans = [
+            self.keep_all_hours.map(|h| h as i64),
+            self.hourly_days.map(|d| d as i64 * 24),
+            self.daily_days.map(|d| d as i64 * 24),
+            self.weekly_weeks.map(|w| w as i64 * 24 * 7),
+        ];
+        let spans: Vec<i64> = spans.into_iter().flatten().collect();
+        if spans.windows(2).any(|w| w[0] > w[1]) {
+            return Err(anyhow!("Retention tiers must cover increasing time spans"));
+        }
+        if self.max_bytes == Some(0) {
+            return Err(anyhow!("Byte cap must be greater than zero"));
+        }
+        if self.squash_window_minutes == Some(0) {
+            return Err(anyhow!("Squash window must be at least one minute"));
+        }
+        Ok(())
+    }
+
+    // Bucket an auto-save of the given age falls into, or None once it is past every tier
+    fn bucket(&self, timestamp: DateTime<Utc>, age: Duration) -> Option<(PruneReason, i64)> {
+        let within = |hours: Option<i64>| hours.is_some_and(|h| age < Duration::hours(h));
+
+        if within(self.hourly_days.map(|d| d as i64 * 24)) {
+            Some((PruneReason::HourlyBucket, timestamp.timestamp().div_euclid(3600)))
+        } else if within(self.daily_days.map(|d| d as i64 * 24)) {
+            Some((PruneReason::DailyBucket, timestamp.date_naive().num_days_from_ce() as i64))
+        } else if within(self.weekly_weeks.map(|w| w as i64 * 24 * 7)) {
+            let week = timestamp.iso_week();
+            Some((PruneReason::WeeklyBucket, week.year() as i64 * 100 + week.week() as i64))
+        } else {
+            None
+        }
+    }
+}
+
+// Why a version would be removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneReason {
+    // A newer auto-save already represents its hour, day or week
+    HourlyBucket,
+    DailyBucket,
+    WeeklyBucket,
+    // Older than every retention tier
+    Expired,
+    // Manual save older than the manual expiry
+    ManualExpired,
+    // Beyond the software's max_versions auto-save count
+    CountLimit,
+    // Removed to bring the history under the byte cap
+    ByteLimit,
+    // Squashed auto-save past its restore window, or whose squashed version is removed
+    Squashed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PruneCandidate {
+    pub version_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub is_auto_save: bool,
+    pub note: Option<String>,
+    pub reason: PruneReason,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrunePlan {
+    pub software_id: String,
+    pub policy: RetentionPolicy,
+    pub candidates: Vec<PruneCandidate>,
+    pub kept_count: usize,
+    pub total_bytes: u64,
+    pub bytes_freed: u64,
+}
+
+impl VersionStorage {
+    // Apply the global policy of the app settings, which own it, to software without an override
+    pub fn follow_retention_settings(&self, policy: RetentionPolicy) {
+        *self.global_retention.write().unwrap_or_else(PoisonError::into_inner) = policy;
+    }
+
+    // Global policy file of releases before the app settings, read once to carry it over
+    pub(super) fn read_legacy_global_policy(&self) -> Result<Option<RetentionPolicy>> {
+        let path = self.base_path().join(GLOBAL_POLICY_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        read_document(DocumentKind::RetentionPolicy, &path).map(Some)
+    }
+
+    pub(super) fn remove_legacy_global_policy(&self) -> Result<()> {
+        let _lock = self.lock()?;
+        let path = self.base_path().join(GLOBAL_POLICY_FILE);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    // The policy in force for a software: its override, else the global policy
+    pub fn get_retention_policy(&self, software_id: &str) -> Result<RetentionPolicy> {
+        let _lock = self.lock()?;
+        let index = self.load_index(software_id)?;
+        self.effective_policy(&index)
+    }
+
+    // Set or clear (`None`) a software's policy override
+    pub fn set_retention_policy(&self, software_id: &str, policy: Option<RetentionPolicy>) -> Result<()> {
+        if let Some(ref policy) = policy {
+            policy.validate()?;
+        }
+
+        let _lock = self.lock()?;
+        let mut index = self.load_index(software_id)?;
+        index.retention = policy;
+        self.save_index(software_id, &index)
+    }
+
+    // Show what pruning would remove without touching anything
+    pub fn prune_preview(&self, software_id: &str) -> Result<PrunePlan> {
+        let _lock = self.lock()?;
+        let index = self.load_index(software_id)?;
+        self.plan_prune(&index, software_id, Utc::now())
+    }
+
+    // Squash finished auto-save runs, then remove every version the retention policy no longer keeps
+    pub fn prune(&self, software_id: &str) -> Result<PrunePlan> {
+        let _lock = self.lock()?;
+        let mut index = self.load_index(software_id)?;
+        let plan = self.apply_retention(&mut index, software_id)?;
+        self.save_index(software_id, &index)?;
+        Ok(plan)
+    }
+
+    pub(super) fn apply_retention(&self, index: &mut VersionIndex, software_id: &str) -> Result<PrunePlan> {
+        let now = Utc::now();
+        if let Some(window) = self.effective_policy(index)?.squash_window_minutes {
+            self.squash_runs(index, software_id, window, Some(now))?;
+        }
+
+        let plan = self.plan_prune(index, software_id, now)?;
+        self.apply_prune(index, software_id, &plan)?;
+        Ok(plan)
+    }
+
+    pub(super) fn effective_policy(&self, index: &VersionIndex) -> Result<RetentionPolicy> {
+        match index.retention {
+            Some(ref policy) => Ok(policy.clone()),
+            None => Ok(self.global_retention.read().unwrap_or_else(PoisonError::into_inner).clone()),
+        }
+    }
+
+    pub(super) fn plan_prune(
+        &self,
+        index: &VersionIndex,
+        software_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<PrunePlan> {
+        let policy = self.effective_policy(index)?;
+        let sizes = self.stored_sizes(software_id, index)?;
+        let mut removals = select_removals(&index.versions, &policy, index.max_versions, &sizes, now);
+        expire_squashed(&index.versions, &index.squashed, &policy, &mut removals, now);
+
+        let total_bytes = sizes.values().sum();
+        let kept_keys: HashSet<String> = index.versions
+            .iter()
+            .chain(&index.squashed)
+            .filter(|v| !removals.contains_key(v.id.as_str()))
+            .map(storage_key)
+            .collect();
+        let bytes_freed = sizes
+            .iter()
+            .filter(|(key, _)| !kept_keys.contains(*key))
+            .map(|(_, size)| size)
+            .sum();
+
+        let kept_count = index.versions
+            .iter()
+            .filter(|v| !removals.contains_key(v.id.as_str()))
+            .count();
+        let candidates = index.versions
+            .iter()
+            .chain(&index.squashed)
+            .filter_map(|v| {
+                removals.get(v.id.as_str()).map(|reason| PruneCandidate {
+                    version_id: v.id.clone(),
+                    timestamp: v.timestamp,
+                    is_auto_save: v.is_auto_save,
+                    note: v.note.clone(),
+                    reason: *reason,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(PrunePlan {
+            software_id: software_id.to_string(),
+            policy,
+            kept_count,
+            candidates,
+            total_bytes,
+            bytes_freed,
+        })
+    }
+
+    pub(super) fn apply_prune(
+        &self,
+        index: &mut VersionIndex,
+        software_id: &str,
+        plan: &PrunePlan,
+    ) -> Result<()> {
+        if plan.candidates.is_empty() {
+            return Ok(());
+        }
+
+        let doomed: HashSet<&str> = plan.candidates.iter().map(|c| c.version_id.as_str()).collect();
+        let (mut removed, kept): (Vec<_>, Vec<_>) = index.versions
+            .drain(..)
+            .partition(|v| doomed.contains(v.id.as_str()));
+        index.versions = kept;
+        let (removed_squashed, kept): (Vec<_>, Vec<_>) = index.squashed
+            .drain(..)
+            .partition(|v| doomed.contains(v.id.as_str()));
+        index.squashed = kept;
+        removed.extend(removed_squashed);
+
+        // Commit the index first so a crash leaves orphans rather than dangling entries
+        self.save_index(software_id, index)?;
+        for version in &removed {
+            self.remove_version_files(software_id, index, version)?;
+        }
+
+        log::info!("Pruned {} versions of {}", removed.len(), software_id);
+        Ok(())
+    }
+
+    // On-disk size of each distinct piece of stored content
+    fn stored_sizes(&self, software_id: &str, index: &VersionIndex) -> Result<HashMap<String, u64>> {
+        let blobs = self.blobs(software_id)?;
+        let mut sizes = HashMap::new();
+
+        for version in index.versions.iter().chain(&index.squashed) {
+            let key = storage_key(version);
+            if sizes.contains_key(&key) {
+                continue;
+            }
+            let path = match version.layout {
+                VersionLayout::Blob => blobs.blob_path(&version.checksum)?,
+                VersionLayout::Inline => self.get_record_path(software_id, &version.file_name)?,
+            };
+            sizes.insert(key, fs::metadata(path).map(|m| m.len()).unwrap_or(0));
+        }
+
+        Ok(sizes)
+    }
+}
+
+// Versions sharing a blob share its bytes
+fn storage_key(version: &VersionMetadata) -> String {
+    match version.layout {
+        VersionLayout::Blob => version.checksum.clone(),
+        VersionLayout::Inline => version.file_name.clone(),
+    }
+}
+
+// Squashed auto-saves stay restorable for the keep-all window, and go with their squashed version
+pub(super) fn expire_squashed<'a>(
+    versions: &[VersionMetadata],
+    squashed: &'a [VersionMetadata],
+    policy: &RetentionPolicy,
+    removals: &mut HashMap<&'a str, PruneReason>,
+    now: DateTime<Utc>,
+) {
+    let restore_window = Duration::hours(policy.keep_all_hours.unwrap_or(DEFAULT_SQUASH_RESTORE_HOURS) as i64);
+    for member in squashed {
+        let survivor = versions
+            .iter()
+            .find(|v| Some(v.id.as_str()) == member.squashed_into.as_deref());
+        let keep = match survivor.and_then(|v| v.squash.as_ref().map(|info| (v, info))) {
+            Some((survivor, info)) => {
+                !removals.contains_key(survivor.id.as_str()) && now - info.squashed_at < restore_window
+            }
+            None => false,
+        };
+        if !keep {
+            removals.insert(member.id.as_str(), PruneReason::Squashed);
+        }
+    }
+}
+
+// Decide which versions to remove and why. The newest version and pinned versions are always kept.
+pub(super) fn select_removals<'a>(
+    versions: &'a [VersionMetadata],
+    policy: &RetentionPolicy,
+    max_auto_saves: usize,
+    sizes: &HashMap<String, u64>,
+    now: DateTime<Utc>,
+) -> HashMap<&'a str, PruneReason> {
+    let newest = versions.last().map(|v| v.id.as_str());
+    let protected = |v: &VersionMetadata| v.pinned || Some(v.id.as_str()) == newest;
+    let mut removals = HashMap::new();
+    let mut buckets = HashSet::new();
+
+    // Time tiers, walking from newest to oldest so each bucket keeps its newest auto-save
+    for version in versions.iter().rev() {
+        let age = now - version.timestamp;
+
+        if !version.is_auto_save {
+            let expired = policy.manual_expiry_days.is_some_and(|d| age > Duration::days(d as i64));
+            if expired && !protected(version) {
+                removals.insert(version.id.as_str(), PruneReason::ManualExpired);
+            }
+            continue;
+        }
+
+        if !policy.has_tiers() || policy.keep_all_hours.is_some_and(|h| age < Duration::hours(h as i64)) {
+            continue;
+        }
+
+        let reason = match policy.bucket(version.timestamp, age) {
+            Some(bucket) if buckets.insert(bucket) => continue,
+            Some((reason, _)) => reason,
+            None => PruneReason::Expired,
+        };
+        if !protected(version) {
+            removals.insert(version.id.as_str(), reason);
+        }
+    }
+
+    // Count cap on the remaining auto-saves
+    let mut auto_saves = 0;
+    for version in versions.iter().rev() {
+        if !version.is_auto_save || version.pinned || removals.contains_key(version.id.as_str()) {
+            continue;
+        }
+        auto_saves += 1;
+        if auto_saves > max_auto_saves && !protected(version) {
+            removals.insert(version.id.as_str(), PruneReason::CountLimit);
+        }
+    }
+
+    // Byte cap: drop the oldest auto-saves, then the oldest manual saves
+    if let Some(max_bytes) = policy.max_bytes {
+        let mut references: HashMap<String, usize> = HashMap::new();
+        for version in versions.iter().filter(|v| !removals.contains_key(v.id.as_str())) {
+            *references.entry(storage_key(version)).or_default() += 1;
+        }
+        let mut total: u64 = references.keys().map(|k| sizes.get(k).copied().unwrap_or(0)).sum();
+
+        let by_age = versions.iter().filter(|v| v.is_auto_save)
+            .chain(versions.iter().filter(|v| !v.is_auto_save));
+        for version in by_age {
+            if total <= max_bytes {
+                break;
+            }
+            if protected(version) || removals.contains_key(version.id.as_str()) {
+                continue;
+            }
+
+            removals.insert(version.id.as_str(), PruneReason::ByteLimit);
+            let key = storage_key(version);
+            let count = references.get_mut(&key).expect("kept version is referenced");
+            *count -= 1;
+            if *count == 0 {
+                total = total.saturating_sub(sizes.get(&key).copied().unwrap_or(0));
+            }
+        }
+    }
+
+    removals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(n: usize, hours_ago: i64, is_auto_save: bool, now: DateTime<Utc>) -> VersionMetadata {
+        VersionMetadata::fixture(n, now - Duration::hours(hours_ago), is_auto_save)
+    }
+
+    #[test]
+    fn test_buckets_keep_newest_per_tier() {
+        let now = "2026-06-15T12:30:00Z".parse::<DateTime<Utc>>().unwrap();
+        // Oldest first, as stored in the index
+        let mut versions = vec![
+            version(0, 24 * 400, true, now),    // past every tier
+            version(1, 24 * 10 + 1, true, now), // same day as v2
+            version(2, 24 * 10, true, now),
+            version(3, 30, true, now),          // hourly tier, own hour
+            version(4, 24 * 60, false, now),    // manual, kept
+            version(5, 2, true, now),           // within keep-all
+            version(6, 1, true, now),
+        ];
+        versions.sort_by_key(|v| v.timestamp);
+
+        let removals = select_removals(&versions, &RetentionPolicy::tiered(), 20, &HashMap::new(), now);
+        let mut removed: Vec<_> = removals.into_iter().collect();
+        removed.sort_by_key(|(id, _)| *id);
+        assert_eq!(removed, vec![("v0", PruneReason::Expired), ("v1", PruneReason::DailyBucket)]);
+
+        // Without opting in to tiers only the count cap thins auto-saves
+        let removals = select_removals(&versions, &RetentionPolicy::default(), 20, &HashMap::new(), now);
+        assert!(removals.is_empty());
+        let removals = select_removals(&versions, &RetentionPolicy::default(), 5, &HashMap::new(), now);
+        assert_eq!(removals.into_iter().collect::<Vec<_>>(), vec![("v0", PruneReason::CountLimit)]);
+    }
+
+    #[test]
+    fn test_pinned_manual_expiry_and_byte_cap() {
+        let now = Utc::now();
+        let mut versions = vec![
+            version(0, 24 * 100, false, now),
+            version(1, 24 * 90, false, now),
+            version(2, 5, true, now),
+            version(3, 4, true, now),
+            version(4, 3, true, now),
+        ];
+        versions[0].pinned = true;
+        let sizes: HashMap<String, u64> = versions.iter().map(|v| (v.checksum.clone(), 100)).collect();
+        let policy = RetentionPolicy {
+            manual_expiry_days: Some(30),
+            max_bytes: Some(300),
+            ..Default::default()
+        };
+
+        let removals = select_removals(&versions, &policy, 20, &sizes, now);
+        assert_eq!(removals.get("v1"), Some(&PruneReason::ManualExpired));
+        assert_eq!(removals.get("v2"), Some(&PruneReason::ByteLimit));
+        assert!(!removals.contains_key("v0"));
+        assert!(!removals.contains_key("v3"));
+        assert!(!removals.contains_key("v4"));
+    }
+
+    #[test]
+    fn test_policy_tiers_must_increase() {
+        assert!(RetentionPolicy::default().validate().is_ok());
+        assert!(RetentionPolicy::tiered().validate().is_ok());
+        let policy = RetentionPolicy { keep_all_hours: Some(24 * 14), ..RetentionPolicy::tiered() };
+        assert!(policy.validate().is_err());
+    }
+}
@@ -0,0 +1,407 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+use crate::software::SoftwareDefinition;
+
+use super::atomic::write_atomic;
+use super::{validate_software_id, BackupSchedule, StorageError, StorageLock, ENCRYPTION_FILE, GLOBAL_POLICY_FILE};
+
+// Field carrying the layout version of every persisted JSON document. Documents written before
+// it existed count as version 0.
+pub const FORMAT_VERSION_FIELD: &str = "format_version";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentKind {
+    VersionIndex,
+    VersionRecord,
+    Preferences,
+    RetentionPolicy,
+    EncryptionHeader,
+}
+
+impl DocumentKind {
+    // Layout version written by this build
+    pub fn current_version(self) -> u32 {
+        match self {
+            DocumentKind::VersionIndex => 3,
+            DocumentKind::VersionRecord => 2,
+            DocumentKind::Preferences => 3,
+            DocumentKind::RetentionPolicy => 1,
+            DocumentKind::EncryptionHeader => 1,
+        }
+    }
+}
+
+// Upgrades a document of one kind from `from` to `from + 1`
+struct Migration {
+    kind: DocumentKind,
+    from: u32,
+    description: &'static str,
+    apply: fn(Value) -> Result<Value>,
+}
+
+// Every layout change gets an entry here, together with a bump of `current_version`
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        kind: DocumentKind::VersionIndex,
+        from: 0,
+        description: "Add the format version",
+        apply: Ok,
+    },
+    Migration {
+        kind: DocumentKind::VersionRecord,
+        from: 0,
+        description: "Add the format version",
+        apply: Ok,
+    },
+    Migration {
+        kind: DocumentKind::VersionIndex,
+        from: 1,
+        description: "Add the digest of sealed secrets to versions",
+        apply: Ok,
+    },
+    Migration {
+        kind: DocumentKind::VersionRecord,
+        from: 1,
+        description: "Add the digest of sealed secrets",
+        apply: Ok,
+    },
+    Migration {
+        kind: DocumentKind::VersionIndex,
+        from: 2,
+        description: "Remember the git commits already imported",
+        apply: record_imported_commits,
+    },
+    Migration {
+        kind: DocumentKind::Preferences,
+        from: 0,
+        description: "Wrap the bare preference list in an object",
+        apply: wrap_preference_list,
+    },
+    Migration {
+        kind: DocumentKind::Preferences,
+        from: 1,
+        description: "Add the backup schedule",
+        apply: add_backup_schedule,
+    },
+    Migration {
+        kind: DocumentKind::Preferences,
+        from: 2,
+        description: "Add the app-wide settings",
+        apply: Ok,
+    },
+    Migration {
+        kind: DocumentKind::RetentionPolicy,
+        from: 0,
+        description: "Add the format version",
+        apply: Ok,
+    },
+    Migration {
+        kind: DocumentKind::EncryptionHeader,
+        from: 0,
+        description: "Add the format version",
+        apply: Ok,
+    },
+];
+
+fn wrap_preference_list(value: Value) -> Result<Value> {
+    match value {
+        Value::Array(_) => Ok(json!({ "preferences": value })),
+        _ => Err(anyhow!("Expected a list of preferences")),
+    }
+}
+
+fn add_backup_schedule(mut value: Value) -> Result<Value> {
+    let map = value.as_object_mut().ok_or_else(|| anyhow!("Expected an object of preferences"))?;
+    if !map.contains_key("backup_schedule") {
+        map.insert("backup_schedule".to_string(), serde_json::to_value(BackupSchedule::default())?);
+    }
+    Ok(value)
+}
+
+fn record_imported_commits(mut value: Value) -> Result<Value> {
+    let map = value.as_object_mut().ok_or_else(|| anyhow!("Expected a version index object"))?;
+    let commits: BTreeSet<String> = ["versions", "squashed"]
+        .iter()
+        .filter_map(|field| map.get(*field).and_then(Value::as_array))
+        .flatten()
+        .filter_map(|version| version.get("source_commit").and_then(Value::as_str))
+        .map(str::to_string)
+        .collect();
+    if !commits.is_empty() {
+        map.insert("imported_commits".to_string(), json!(commits));
+    }
+    Ok(value)
+}
+
+fn version_of(value: &Value) -> u32 {
+    value
+        .get(FORMAT_VERSION_FIELD)
+        .and_then(Value::as_u64)
+        .map_or(0, |version| version as u32)
+}
+
+// Bring a parsed document up to the current layout, refusing documents from a newer build
+pub(super) fn upgrade(kind: DocumentKind, mut value: Value, path: &Path) -> Result<Value> {
+    let current = kind.current_version();
+    let mut version = version_of(&value);
+    if version > current {
+        return Err(StorageError::UnsupportedFormat {
+            path: path.display().to_string(),
+            found: version,
+            supported: current,
+        }
+        .into());
+    }
+
+    while version < current {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|m| m.kind == kind && m.from == version)
+            .ok_or_else(|| anyhow!("No migration for {:?} documents from format {}", kind, version))?;
+        value = (migration.apply)(value)
+            .with_context(|| format!("Failed to upgrade {:?}: {}", path, migration.description))?;
+        version += 1;
+
+        if let Value::Object(map) = &mut value {
+            map.insert(FORMAT_VERSION_FIELD.to_string(), version.into());
+        }
+    }
+
+    Ok(value)
+}
+
+pub(super) fn parse_document<T: DeserializeOwned>(kind: DocumentKind, content: &str, path: &Path) -> Result<T> {
+    let value = serde_json::from_str(content)?;
+    Ok(serde_json::from_value(upgrade(kind, value, path)?)?)
+}
+
+pub(super) fn read_document<T: DeserializeOwned>(kind: DocumentKind, path: &Path) -> Result<T> {
+    parse_document(kind, &fs::read_to_string(path)?, path)
+}
+
+// Write a document stamped with the current format, never replacing one from a newer build
+pub(super) fn write_document<T: Serialize>(kind: DocumentKind, path: &Path, document: &T) -> Result<()> {
+    if let Some(existing) = fs::read_to_string(path).ok().and_then(|c| serde_json::from_str::<Value>(&c).ok()) {
+        if version_of(&existing) > kind.current_version() {
+            return Err(StorageError::UnsupportedFormat {
+                path: path.display().to_string(),
+                found: version_of(&existing),
+                supported: kind.current_version(),
+            }
+            .into());
+        }
+    }
+
+    let mut value = serde_json::to_value(document)?;
+    let map = value
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("{:?} documents must be JSON objects", kind))?;
+    map.insert(FORMAT_VERSION_FIELD.to_string(), kind.current_version().into());
+
+    write_atomic(path, serde_json::to_string_pretty(&value)?)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpgradedDocument {
+    pub path: String,
+    pub kind: DocumentKind,
+    pub from: u32,
+    pub to: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchemaMigrationReport {
+    // Copies of the documents as they were before upgrading
+    pub backup_dir: Option<String>,
+    pub upgraded: Vec<UpgradedDocument>,
+}
+
+pub struct SchemaMigrator;
+
+impl SchemaMigrator {
+    // Upgrade the documents in the app data directory and the history directory named by the
+    // settings before any storage reads them
+    pub fn migrate_app_data(app_handle: &tauri::AppHandle) -> Result<SchemaMigrationReport> {
+        let app_dir = app_handle
+            .path()
+            .app_data_dir()
+            .context("Failed to get app data directory")?;
+        let versions_dir = match Self::storage_location(&app_dir) {
+            Some(location) => PathBuf::from(SoftwareDefinition::expand_path(&location)),
+            None => app_dir.join("versions"),
+        };
+
+        Self::migrate_dir(&app_dir, &versions_dir)
+    }
+
+    // The history directory from the stored settings, read before the preferences are upgraded
+    fn storage_location(app_dir: &Path) -> Option<String> {
+        let content = fs::read_to_string(app_dir.join("preferences.json")).ok()?;
+        let value: Value = serde_json::from_str(&content).ok()?;
+        value.get("settings")?.get("storage_location")?.as_str().map(str::to_string)
+    }
+
+    // Rewrite every outdated document in the current format after copying the originals to a
+    // backup directory. Nothing is changed if any document comes from a newer build.
+    pub fn migrate_dir(app_dir: &Path, versions_dir: &Path) -> Result<SchemaMigrationReport> {
+        fs::create_dir_all(versions_dir)?;
+        let versions_lock = StorageLock::new(versions_dir.join(".lock"));
+        let preferences_lock = StorageLock::new(app_dir.join("preferences.json.lock"));
+        let _versions_guard = versions_lock.acquire()?;
+        let _preferences_guard = preferences_lock.acquire()?;
+
+        let mut outdated = Vec::new();
+        for (path, kind) in Self::documents(app_dir, versions_dir)? {
+            // Unreadable documents are left to the storage that owns them to repair
+            let value = match fs::read_to_string(&path).ok().and_then(|c| serde_json::from_str::<Value>(&c).ok()) {
+                Some(value) => value,
+                None => continue,
+            };
+            let version = version_of(&value);
+            if version > kind.current_version() {
+                return Err(StorageError::UnsupportedFormat {
+                    path: path.display().to_string(),
+                    found: version,
+                    supported: kind.current_version(),
+                }
+                .into());
+            }
+            if version < kind.current_version() {
+                outdated.push((path, kind, value, version));
+            }
+        }
+
+        let mut report = SchemaMigrationReport::default();
+        if outdated.is_empty() {
+            return Ok(report);
+        }
+
+        let backup_dir = app_dir
+            .join("schema-backups")
+            .join(Utc::now().format("%Y%m%dT%H%M%S").to_string());
+        for (path, ..) in &outdated {
+            // History kept outside the app data directory is backed up under `versions` as well
+            let relative = match path.strip_prefix(versions_dir) {
+                Ok(relative) => Path::new("versions").join(relative),
+                Err(_) => path.strip_prefix(app_dir)?.to_path_buf(),
+            };
+            let target = backup_dir.join(relative);
+            fs::create_dir_all(target.parent().unwrap_or(&backup_dir))?;
+            fs::copy(path, target)?;
+        }
+
+        for (path, kind, value, version) in outdated {
+            let upgraded = upgrade(kind, value, &path)?;
+            write_atomic(&path, serde_json::to_string_pretty(&upgraded)?)?;
+            report.upgraded.push(UpgradedDocument {
+                path: path.display().to_string(),
+                kind,
+                from: version,
+                to: kind.current_version(),
+            });
+        }
+
+        log::info!(
+            "Upgraded {} stored documents; originals are in {:?}",
+            report.upgraded.len(),
+            backup_dir
+        );
+        report.backup_dir = Some(backup_dir.display().to_string());
+        Ok(report)
+    }
+
+    // Every JSON document the storages persist under the app data and history directories
+    fn documents(app_dir: &Path, versions_dir: &Path) -> Result<Vec<(PathBuf, DocumentKind)>> {
+        let mut documents = Vec::new();
+
+        for (path, kind) in [
+            (app_dir.join("preferences.json"), DocumentKind::Preferences),
+            (versions_dir.join(GLOBAL_POLICY_FILE), DocumentKind::RetentionPolicy),
+        ] {
+            if path.exists() {
+                documents.push((path, kind));
+            }
+        }
+
+        for entry in fs::read_dir(versions_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !entry.file_type()?.is_dir() || validate_software_id(&name).is_err() {
+                continue;
+            }
+
+            for file in fs::read_dir(entry.path())? {
+                let path = file?.path();
+                let kind = match path.file_name().and_then(|n| n.to_str()) {
+                    Some("index.json") => DocumentKind::VersionIndex,
+                    Some(ENCRYPTION_FILE) => DocumentKind::EncryptionHeader,
+                    Some(name) if name.ends_with(".json") => DocumentKind::VersionRecord,
+                    _ => continue,
+                };
+                documents.push((path, kind));
+            }
+        }
+
+        Ok(documents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_old_documents_are_upgraded_after_a_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        // History kept outside the app data directory is upgraded with it
+        let app_dir = dir.path().join("app");
+        let versions = dir.path().join("history");
+        fs::create_dir_all(&app_dir).unwrap();
+        let preferences = app_dir.join("preferences.json");
+        fs::write(&preferences, r#"[{"software_id": "zsh", "preferred_editor": "source",
+            "show_advanced": false, "auto_save": true, "auto_backup": true, "backup_count": 5}]"#).unwrap();
+        fs::create_dir_all(versions.join("zsh")).unwrap();
+        fs::write(versions.join("zsh/index.json"), r#"{"versions": [], "max_versions": 7}"#).unwrap();
+
+        let report = SchemaMigrator::migrate_dir(&app_dir, &versions).unwrap();
+        assert_eq!(report.upgraded.len(), 2);
+        let backup = PathBuf::from(report.backup_dir.unwrap());
+        assert!(backup.join("preferences.json").exists());
+        assert!(backup.join("versions/zsh/index.json").exists());
+
+        let upgraded: Value = serde_json::from_str(&fs::read_to_string(&preferences).unwrap()).unwrap();
+        assert_eq!(upgraded[FORMAT_VERSION_FIELD], 3);
+        assert_eq!(upgraded["preferences"][0]["backup_count"], 5);
+        assert_eq!(upgraded["backup_schedule"]["on_start"], true);
+        assert!(SchemaMigrator::migrate_dir(&app_dir, &versions).unwrap().upgraded.is_empty());
+
+        // A document from a newer build is refused and left as it is
+        let newer = r#"{"format_version": 99, "versions": [], "max_versions": 7}"#;
+        fs::write(versions.join("zsh/index.json"), newer).unwrap();
+        let error = SchemaMigrator::migrate_dir(&app_dir, &versions).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(StorageError::UnsupportedFormat { found: 99, .. })));
+        let storage = crate::storage::VersionStorage::with_base_path(versions.clone()).unwrap();
+        assert!(storage.save_version("zsh", "a\n", None, false).is_err());
+        assert_eq!(fs::read_to_string(versions.join("zsh/index.json")).unwrap(), newer);
+    }
+
+    #[test]
+    fn test_imported_commits_are_taken_from_the_versions() {
+        let index = json!({
+            "versions": [{ "source_commit": "b" }, { "source_commit": null }],
+            "squashed": [{ "source_commit": "a" }],
+        });
+        let upgraded = record_imported_commits(index).unwrap();
+        assert_eq!(upgraded["imported_commits"], json!(["a", "b"]));
+    }
+}
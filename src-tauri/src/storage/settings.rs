@@ -0,0 +1,257 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use super::{PreferencesStorage, RetentionPolicy, SoftwarePreferences, VersionStorage};
+
+// Event emitted to the frontend whenever the settings are saved
+pub const SETTINGS_CHANGED_EVENT: &str = "app-settings-changed";
+
+// Preferences a software inherits until it saves its own
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SoftwareDefaults {
+    pub preferred_editor: String, // "form" or "source"
+    pub show_advanced: bool,
+    pub auto_save: bool,
+    pub auto_backup: bool,
+    pub backup_count: usize,
+}
+
+impl Default for SoftwareDefaults {
+    fn default() -> Self {
+        let preferences = SoftwarePreferences::default();
+        Self {
+            preferred_editor: preferences.preferred_editor,
+            show_advanced: preferences.show_advanced,
+            auto_save: preferences.auto_save,
+            auto_backup: preferences.auto_backup,
+            backup_count: preferences.backup_count,
+        }
+    }
+}
+
+impl SoftwareDefaults {
+    pub fn for_software(&self, software_id: &str) -> SoftwarePreferences {
+        SoftwarePreferences {
+            software_id: software_id.to_string(),
+            preferred_editor: self.preferred_editor.clone(),
+            show_advanced: self.show_advanced,
+            auto_save: self.auto_save,
+            auto_backup: self.auto_backup,
+            backup_count: self.backup_count,
+        }
+    }
+}
+
+// App-wide settings, kept in the preferences file next to the per-software entries
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    // Retention for software without a policy of their own
+    pub retention: RetentionPolicy,
+    // Directory holding version history; `None` uses the app data directory. Read at startup.
+    pub storage_location: Option<String>,
+    // Whether the frontend watches managed files for outside changes
+    pub watcher_enabled: bool,
+    // Verify version storage in the background when the app starts
+    pub verify_on_start: bool,
+    pub locale: String,
+    // Directories searched for additional software definitions, one `*.json` file each
+    pub definition_dirs: Vec<String>,
+    pub software_defaults: SoftwareDefaults,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            retention: RetentionPolicy::default(),
+            storage_location: None,
+            watcher_enabled: true,
+            verify_on_start: false,
+            locale: "en".to_string(),
+            definition_dirs: Vec::new(),
+            software_defaults: SoftwareDefaults::default(),
+        }
+    }
+}
+
+// Payload of SETTINGS_CHANGED_EVENT
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsChange {
+    pub settings: AppSettings,
+    // Top-level fields that differ from the previous settings
+    pub changed: Vec<String>,
+}
+
+// A software's preferences and whether they come from the global defaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectivePreferences {
+    pub preferences: SoftwarePreferences,
+    pub inherited: bool,
+}
+
+impl AppSettings {
+    pub fn validate(&self) -> Result<()> {
+        self.retention.validate()?;
+
+        if let Some(ref location) = self.storage_location {
+            validate_directory("Storage location", location)?;
+        }
+
+        static LOCALE: OnceLock<Regex> = OnceLock::new();
+        let locale = LOCALE.get_or_init(|| Regex::new(r"^[a-z]{2,3}(-[A-Za-z0-9]{2,8})*$").unwrap());
+        if !locale.is_match(&self.locale) {
+            return Err(anyhow!("Invalid locale: {:?}", self.locale));
+        }
+
+        let mut seen = HashSet::new();
+        for dir in &self.definition_dirs {
+            validate_directory("Definition directory", dir)?;
+            if !seen.insert(dir) {
+                return Err(anyhow!("Definition directory {:?} is listed twice", dir));
+            }
+        }
+
+        let defaults = &self.software_defaults;
+        if !matches!(defaults.preferred_editor.as_str(), "form" | "source") {
+            return Err(anyhow!("Preferred editor must be \"form\" or \"source\""));
+        }
+        if defaults.backup_count == 0 {
+            return Err(anyhow!("Backup count must be at least 1"));
+        }
+
+        Ok(())
+    }
+
+    // Names of the top-level fields that differ between two settings
+    pub fn changed_fields(&self, previous: &AppSettings) -> Result<Vec<String>> {
+        let current = serde_json::to_value(self)?;
+        let previous = serde_json::to_value(previous)?;
+
+        Ok(current
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(key, value)| previous.get(key.as_str()) != Some(*value))
+            .map(|(key, _)| key.clone())
+            .collect())
+    }
+}
+
+// Paths may be absolute or start at the home directory
+fn validate_directory(label: &str, path: &str) -> Result<()> {
+    if path.starts_with("~/") || Path::new(path).is_absolute() {
+        Ok(())
+    } else {
+        Err(anyhow!("{} must be an absolute path: {:?}", label, path))
+    }
+}
+
+// History is not moved along with the storage location, so it may only change while there is none
+pub fn check_location_change(previous: &AppSettings, settings: &AppSettings, storage: &VersionStorage) -> Result<()> {
+    if settings.storage_location != previous.storage_location && storage.has_history()? {
+        return Err(anyhow!(
+            "Version history exists in {}; it is not moved, so the storage location cannot change",
+            storage.base_path().display()
+        ));
+    }
+    Ok(())
+}
+
+// Load the settings and apply their retention to the storage. The global policy file of older
+// releases is carried over the first time, then removed so the settings stay its only owner.
+pub fn initialize_settings(preferences: &PreferencesStorage, storage: &VersionStorage) -> Result<AppSettings> {
+    let settings = match preferences.stored_settings()? {
+        Some(settings) => settings,
+        None => {
+            let settings = AppSettings {
+                retention: storage.read_legacy_global_policy()?.unwrap_or_default(),
+                ..Default::default()
+            };
+            preferences.save_settings(settings.clone())?;
+            settings
+        }
+    };
+    storage.remove_legacy_global_policy()?;
+
+    storage.follow_retention_settings(settings.retention.clone());
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::schema::write_document;
+    use crate::storage::{DocumentKind, PreferenceStore, GLOBAL_POLICY_FILE};
+
+    #[test]
+    fn test_settings_are_validated_and_inherited() {
+        let dir = tempfile::tempdir().unwrap();
+        let preferences = PreferencesStorage::with_file_path(dir.path().join("preferences.json"));
+
+        let invalid = [
+            AppSettings { locale: "English".to_string(), ..Default::default() },
+            AppSettings { storage_location: Some("relative/dir".to_string()), ..Default::default() },
+            AppSettings { definition_dirs: vec!["~/defs".to_string(), "~/defs".to_string()], ..Default::default() },
+        ];
+        for settings in invalid {
+            assert!(preferences.save_settings(settings).is_err());
+        }
+
+        let mut settings = AppSettings { locale: "pt-BR".to_string(), ..Default::default() };
+        settings.software_defaults.backup_count = 40;
+        preferences.save_settings(settings.clone()).unwrap();
+        assert_eq!(settings.changed_fields(&AppSettings::default()).unwrap(), vec!["locale", "software_defaults"]);
+
+        // Software without preferences of their own follow the defaults until they save some
+        let zsh = preferences.resolve_preferences("zsh").unwrap();
+        assert!(zsh.inherited);
+        assert_eq!(zsh.preferences.backup_count, 40);
+
+        preferences
+            .save_preferences(SoftwarePreferences { backup_count: 5, ..zsh.preferences })
+            .unwrap();
+        assert_eq!(preferences.get_preferences("zsh").unwrap().backup_count, 5);
+        assert!(!preferences.resolve_preferences("zsh").unwrap().inherited);
+
+        assert!(preferences.reset_preferences("zsh").unwrap());
+        assert_eq!(preferences.get_preferences("zsh").unwrap().backup_count, 40);
+    }
+
+    #[test]
+    fn test_legacy_global_policy_is_carried_over_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = VersionStorage::with_base_path(dir.path().join("versions")).unwrap();
+        let preferences = PreferencesStorage::with_file_path(dir.path().join("preferences.json"));
+        let legacy = dir.path().join("versions").join(GLOBAL_POLICY_FILE);
+        let policy = RetentionPolicy::tiered();
+        write_document(DocumentKind::RetentionPolicy, &legacy, &policy).unwrap();
+
+        let settings = initialize_settings(&preferences, &storage).unwrap();
+        assert_eq!(settings.retention, policy);
+        assert!(!legacy.exists());
+        assert_eq!(storage.get_retention_policy("zsh").unwrap(), policy);
+
+        // From then on the settings are the only record of the global policy
+        preferences.save_settings(AppSettings::default()).unwrap();
+        initialize_settings(&preferences, &storage).unwrap();
+        assert_eq!(storage.get_retention_policy("zsh").unwrap(), RetentionPolicy::default());
+    }
+
+    #[test]
+    fn test_storage_location_only_changes_without_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = VersionStorage::with_base_path(dir.path().join("versions")).unwrap();
+        let moved = AppSettings { storage_location: Some("~/history".to_string()), ..Default::default() };
+
+        storage.set_max_versions("zsh", 5).unwrap();
+        assert!(check_location_change(&AppSettings::default(), &moved, &storage).is_ok());
+
+        storage.save_version("zsh", "a\n", None, false).unwrap();
+        assert!(check_location_change(&AppSettings::default(), &moved, &storage).is_err());
+        assert!(check_location_change(&moved, &moved, &storage).is_ok());
+    }
+}
@@ -0,0 +1,349 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use std::ops::Range;
+
+use super::{validate_version_id, VersionIndex, VersionMetadata, VersionStorage};
+
+// Largest gap between auto-saves of one editing session, unless the policy says otherwise
+pub const DEFAULT_SQUASH_WINDOW_MINUTES: u32 = 15;
+
+// Recorded on the version a run of auto-saves was squashed into
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SquashInfo {
+    // Ids of the hidden auto-saves, oldest first
+    pub members: Vec<String>,
+    // Note of this version before squashing, restored by unsquashing
+    pub original_note: Option<String>,
+    // Time of the first auto-save in the run
+    pub first_timestamp: DateTime<Utc>,
+    // Line changes across the whole run
+    pub lines_added: usize,
+    pub lines_removed: usize,
+    pub squashed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SquashedRun {
+    // The newest auto-save of the run, which stays visible
+    pub version_id: String,
+    // Auto-saves hidden by this squash
+    pub hidden: Vec<String>,
+    pub note: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SquashReport {
+    pub software_id: String,
+    pub window_minutes: u32,
+    pub runs: Vec<SquashedRun>,
+}
+
+impl VersionStorage {
+    // Coalesce every run of consecutive auto-saves, including one still in progress. Without a
+    // window, the retention policy's squash window is used.
+    pub fn squash_auto_saves(&self, software_id: &str, window_minutes: Option<u32>) -> Result<SquashReport> {
+        let _lock = self.lock()?;
+        let mut index = self.load_index(software_id)?;
+
+        let window_minutes = match window_minutes {
+            Some(minutes) => minutes,
+            None => self
+                .effective_policy(&index)?
+                .squash_window_minutes
+                .unwrap_or(DEFAULT_SQUASH_WINDOW_MINUTES),
+        };
+        if window_minutes == 0 {
+            return Err(anyhow!("Squash window must be at least one minute"));
+        }
+
+        let runs = self.squash_runs(&mut index, software_id, window_minutes, None)?;
+        self.save_index(software_id, &index)?;
+
+        Ok(SquashReport {
+            software_id: software_id.to_string(),
+            window_minutes,
+            runs,
+        })
+    }
+
+    // Bring back the auto-saves squashed into a version that pruning has not removed yet
+    pub fn unsquash_version(&self, software_id: &str, version_id: &str) -> Result<Vec<String>> {
+        validate_version_id(version_id)?;
+        let _lock = self.lock()?;
+        let mut index = self.load_index(software_id)?;
+
+        let position = index.versions
+            .iter()
+            .position(|v| v.id == version_id)
+            .context("Version not found")?;
+        let info = index.versions[position]
+            .squash
+            .clone()
+            .ok_or_else(|| anyhow!("Version {} is not a squashed version", version_id))?;
+
+        let (restored, squashed): (Vec<_>, Vec<_>) = index.squashed
+            .drain(..)
+            .partition(|v| v.squashed_into.as_deref() == Some(version_id));
+        index.squashed = squashed;
+
+        let survivor = &mut index.versions[position];
+        survivor.note = info.original_note;
+        survivor.squash = None;
+        self.write_record(software_id, survivor)?;
+
+        let mut ids = Vec::new();
+        for mut version in restored {
+            version.squashed_into = None;
+            self.write_record(software_id, &version)?;
+            ids.push(version.id.clone());
+
+            let position = index.versions.partition_point(|v| v.timestamp <= version.timestamp);
+            index.versions.insert(position, version);
+        }
+        self.save_index(software_id, &index)?;
+
+        if ids.len() < info.members.len() {
+            log::info!(
+                "Unsquashed {} of {} auto-saves into {}; the rest were pruned",
+                ids.len(), info.members.len(), version_id
+            );
+        }
+        Ok(ids)
+    }
+
+    // Squash the runs found in the index without saving it. With `now`, a run that may still be
+    // growing is left alone.
+    pub(super) fn squash_runs(
+        &self,
+        index: &mut VersionIndex,
+        software_id: &str,
+        window_minutes: u32,
+        now: Option<DateTime<Utc>>,
+    ) -> Result<Vec<SquashedRun>> {
+        let (runs, changed) = squash_versions(
+            &mut index.versions,
+            &mut index.squashed,
+            window_minutes,
+            now,
+            |metadata| Ok(self.read_version(software_id, metadata)?.content),
+        )?;
+        for version in &changed {
+            self.write_record(software_id, version)?;
+        }
+
+        if !runs.is_empty() {
+            log::info!("Squashed {} auto-save runs of {}", runs.len(), software_id);
+        }
+        Ok(runs)
+    }
+}
+
+// Squash the runs of auto-saves in `versions`, moving the hidden ones to `squashed`. Returns the
+// runs and every metadata record that changed, for the caller to persist.
+pub(super) fn squash_versions(
+    versions: &mut Vec<VersionMetadata>,
+    squashed: &mut Vec<VersionMetadata>,
+    window_minutes: u32,
+    now: Option<DateTime<Utc>>,
+    mut content: impl FnMut(&VersionMetadata) -> Result<String>,
+) -> Result<(Vec<SquashedRun>, Vec<VersionMetadata>)> {
+    let ranges = find_runs(versions, Duration::minutes(window_minutes as i64), now);
+    let squashed_at = Utc::now();
+    let mut runs = Vec::new();
+    let mut changed = Vec::new();
+
+    // Walk backwards so earlier ranges keep their positions
+    for range in ranges.into_iter().rev() {
+        let before = match range.start {
+            0 => String::new(),
+            start => content(&versions[start - 1])?,
+        };
+        let last = range.end - 1;
+        let after = content(&versions[last])?;
+        let (lines_added, lines_removed) = line_changes(&before, &after);
+
+        let mut hidden: Vec<VersionMetadata> = versions.drain(range.start..last).collect();
+        let survivor_id = versions[range.start].id.clone();
+        let first_timestamp = hidden[0].squash.as_ref().map_or(hidden[0].timestamp, |i| i.first_timestamp);
+
+        // Earlier squashes inside the run are flattened into this one
+        let mut members = Vec::new();
+        for version in &mut hidden {
+            if let Some(info) = version.squash.take() {
+                version.note = info.original_note;
+                members.extend(info.members);
+            }
+            version.squashed_into = Some(survivor_id.clone());
+            members.push(version.id.clone());
+        }
+
+        let survivor = &mut versions[range.start];
+        let original_note = match survivor.squash.take() {
+            Some(info) => {
+                members.extend(info.members);
+                info.original_note
+            }
+            None => survivor.note.clone(),
+        };
+
+        let note = squash_note(members.len() + 1, survivor.timestamp - first_timestamp, lines_added, lines_removed);
+        survivor.note = Some(note.clone());
+        survivor.squash = Some(SquashInfo {
+            members,
+            original_note,
+            first_timestamp,
+            lines_added,
+            lines_removed,
+            squashed_at,
+        });
+        changed.push(survivor.clone());
+
+        let hidden_ids: Vec<String> = hidden.iter().map(|v| v.id.clone()).collect();
+        for version in squashed.iter_mut() {
+            if version.squashed_into.as_ref().is_some_and(|id| hidden_ids.contains(id)) {
+                version.squashed_into = Some(survivor_id.clone());
+                changed.push(version.clone());
+            }
+        }
+        changed.extend(hidden.iter().cloned());
+        squashed.extend(hidden);
+
+        runs.push(SquashedRun {
+            version_id: survivor_id,
+            hidden: hidden_ids,
+            note,
+        });
+    }
+
+    if !runs.is_empty() {
+        squashed.sort_by_key(|v| v.timestamp);
+    }
+    runs.reverse();
+    Ok((runs, changed))
+}
+
+// Ranges of at least two consecutive plain auto-saves, each within `window` of the previous one.
+// Pinned, tagged or labelled versions are never squashed.
+fn find_runs(versions: &[VersionMetadata], window: Duration, now: Option<DateTime<Utc>>) -> Vec<Range<usize>> {
+    let squashable = |v: &VersionMetadata| v.is_auto_save && !v.pinned && v.tags.is_empty() && v.labels.is_empty();
+    let mut runs = Vec::new();
+    let mut i = 0;
+
+    while i < versions.len() {
+        if !squashable(&versions[i]) {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i + 1 < versions.len()
+            && squashable(&versions[i + 1])
+            && versions[i + 1].timestamp - versions[i].timestamp <= window
+        {
+            i += 1;
+        }
+
+        let end = i + 1;
+        let open = end == versions.len() && now.is_some_and(|now| now - versions[i].timestamp <= window);
+        if end - start > 1 && !open {
+            runs.push(start..end);
+        }
+        i = end;
+    }
+
+    runs
+}
+
+fn line_changes(before: &str, after: &str) -> (usize, usize) {
+    let diff = TextDiff::from_lines(before, after);
+    diff.iter_all_changes().fold((0, 0), |(added, removed), change| match change.tag() {
+        ChangeTag::Insert => (added + 1, removed),
+        ChangeTag::Delete => (added, removed + 1),
+        ChangeTag::Equal => (added, removed),
+    })
+}
+
+// e.g. "Squashed 12 auto-saves over 40 minutes (+8 -3 lines)"
+fn squash_note(count: usize, span: Duration, lines_added: usize, lines_removed: usize) -> String {
+    let minutes = span.num_minutes().max(1);
+    format!(
+        "Squashed {} auto-saves over {} minute{} (+{} -{} lines)",
+        count,
+        minutes,
+        if minutes == 1 { "" } else { "s" },
+        lines_added,
+        lines_removed
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(n: usize, minutes: i64, is_auto_save: bool) -> VersionMetadata {
+        let start = "2026-06-15T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        VersionMetadata::fixture(n, start + Duration::minutes(minutes), is_auto_save)
+    }
+
+    #[test]
+    fn test_runs_break_on_gaps_manual_saves_and_pins() {
+        let mut versions = vec![
+            version(0, 0, true),
+            version(1, 5, true),
+            version(2, 10, true),
+            version(3, 60, true),  // gap
+            version(4, 61, false), // manual save
+            version(5, 62, true),
+            version(6, 63, true),
+            version(7, 64, true),  // pinned
+            version(8, 65, true),
+            version(9, 66, true),
+        ];
+        versions[7].pinned = true;
+
+        let window = Duration::minutes(15);
+        assert_eq!(find_runs(&versions, window, None), vec![0..3, 5..7, 8..10]);
+
+        // The last run may still be growing
+        let now = versions[9].timestamp + Duration::minutes(5);
+        assert_eq!(find_runs(&versions, window, Some(now)), vec![0..3, 5..7]);
+    }
+
+    #[test]
+    fn test_squash_is_reversible_until_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = VersionStorage::with_base_path(dir.path().to_path_buf()).unwrap();
+        storage.set_retention_policy("zsh", Some(crate::storage::RetentionPolicy {
+            squash_window_minutes: None,
+            ..Default::default()
+        })).unwrap();
+
+        storage.save_version("zsh", "a\n", Some("Initial".into()), false).unwrap();
+        for content in ["a\nb\n", "a\nb\nc\n", "a\nB\nc\n"] {
+            storage.save_version("zsh", content, None, true).unwrap();
+        }
+
+        let report = storage.squash_auto_saves("zsh", Some(15)).unwrap();
+        assert_eq!(report.runs.len(), 1);
+        assert_eq!(report.runs[0].hidden.len(), 2);
+        assert_eq!(report.runs[0].note, "Squashed 3 auto-saves over 1 minute (+2 -0 lines)");
+
+        let history = storage.get_history("zsh", None).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].content, "a\nB\nc\n");
+
+        // Rebuilding the index keeps the hidden auto-saves hidden
+        storage.rebuild_index("zsh").unwrap();
+        assert_eq!(storage.get_history("zsh", None).unwrap().len(), 2);
+        assert!(storage.prune_preview("zsh").unwrap().candidates.is_empty());
+
+        let restored = storage.unsquash_version("zsh", &report.runs[0].version_id).unwrap();
+        assert_eq!(restored, report.runs[0].hidden);
+        let history = storage.get_history("zsh", None).unwrap();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[0].note, None);
+        assert!(storage.verify_storage().unwrap().healthy);
+    }
+}
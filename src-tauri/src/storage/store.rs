@@ -0,0 +1,203 @@
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeSet, HashSet};
+
+use crate::software::ConfigVersion;
+
+use super::{
+    validate_version_id, ChecksumAlgorithm, HistoryPage, HistoryQuery, RetentionPolicy, SecretPolicy,
+    SoftwarePreferences, VersionLayout, VersionMetadata, VersionStorage,
+};
+
+// Everything stored for one software, used to move history between backends
+#[derive(Debug, Clone)]
+pub struct SoftwareDump {
+    pub software_id: String,
+    pub max_versions: usize,
+    pub retention: Option<RetentionPolicy>,
+    pub secret_policy: Option<SecretPolicy>,
+    // Git commits already imported, so pruned ones are not imported again
+    pub imported_commits: BTreeSet<String>,
+    // Every version including squashed ones, oldest first, with its content
+    pub versions: Vec<(VersionMetadata, String)>,
+}
+
+// Persistence of version history, independent of where it is kept
+pub trait VersionStore: Send + Sync {
+    fn save_version(
+        &self,
+        software_id: &str,
+        content: &str,
+        note: Option<String>,
+        is_auto_save: bool,
+    ) -> Result<ConfigVersion>;
+
+    // Newest first
+    fn get_history(&self, software_id: &str, limit: Option<usize>) -> Result<Vec<ConfigVersion>>;
+
+    fn get_version(&self, software_id: &str, version_id: &str) -> Result<ConfigVersion>;
+
+    fn get_latest_version(&self, software_id: &str) -> Result<Option<ConfigVersion>>;
+
+    // The version saved immediately before another one
+    fn get_previous_version(&self, software_id: &str, version_id: &str) -> Result<Option<ConfigVersion>>;
+
+    fn delete_version(&self, software_id: &str, version_id: &str) -> Result<()>;
+
+    fn query_history(&self, software_id: &str, query: &HistoryQuery) -> Result<HistoryPage>;
+
+    // Newest first
+    fn get_tagged_versions(&self, software_id: &str, tag: &str) -> Result<Vec<ConfigVersion>>;
+
+    fn get_tagged_version(&self, software_id: &str, tag: &str) -> Result<Option<ConfigVersion>> {
+        Ok(self.get_tagged_versions(software_id, tag)?.into_iter().next())
+    }
+
+    fn set_max_versions(&self, software_id: &str, max_versions: usize) -> Result<()>;
+
+    fn get_max_versions(&self, software_id: &str) -> Result<usize>;
+
+    // Software with stored history, sorted
+    fn software_ids(&self) -> Result<Vec<String>>;
+
+    fn dump_software(&self, software_id: &str) -> Result<SoftwareDump>;
+
+    // Add the dumped versions this store does not have yet, returning how many were added
+    fn load_dump(&self, dump: &SoftwareDump) -> Result<usize>;
+}
+
+// Persistence of per-software preferences
+pub trait PreferenceStore: Send + Sync {
+    fn get_preferences(&self, software_id: &str) -> Result<SoftwarePreferences>;
+
+    fn save_preferences(&self, preferences: SoftwarePreferences) -> Result<()>;
+
+    fn list_preferences(&self) -> Result<Vec<SoftwarePreferences>>;
+}
+
+impl VersionStore for VersionStorage {
+    fn save_version(
+        &self,
+        software_id: &str,
+        content: &str,
+        note: Option<String>,
+        is_auto_save: bool,
+    ) -> Result<ConfigVersion> {
+        VersionStorage::save_version(self, software_id, content, note, is_auto_save)
+    }
+
+    fn get_history(&self, software_id: &str, limit: Option<usize>) -> Result<Vec<ConfigVersion>> {
+        VersionStorage::get_history(self, software_id, limit)
+    }
+
+    fn get_version(&self, software_id: &str, version_id: &str) -> Result<ConfigVersion> {
+        VersionStorage::get_version(self, software_id, version_id)
+    }
+
+    fn get_latest_version(&self, software_id: &str) -> Result<Option<ConfigVersion>> {
+        VersionStorage::get_latest_version(self, software_id)
+    }
+
+    fn get_previous_version(&self, software_id: &str, version_id: &str) -> Result<Option<ConfigVersion>> {
+        VersionStorage::get_previous_version(self, software_id, version_id)
+    }
+
+    fn delete_version(&self, software_id: &str, version_id: &str) -> Result<()> {
+        VersionStorage::delete_version(self, software_id, version_id)
+    }
+
+    fn query_history(&self, software_id: &str, query: &HistoryQuery) -> Result<HistoryPage> {
+        VersionStorage::query_history(self, software_id, query)
+    }
+
+    fn get_tagged_versions(&self, software_id: &str, tag: &str) -> Result<Vec<ConfigVersion>> {
+        VersionStorage::get_tagged_versions(self, software_id, tag)
+    }
+
+    fn get_tagged_version(&self, software_id: &str, tag: &str) -> Result<Option<ConfigVersion>> {
+        VersionStorage::get_tagged_version(self, software_id, tag)
+    }
+
+    fn set_max_versions(&self, software_id: &str, max_versions: usize) -> Result<()> {
+        VersionStorage::set_max_versions(self, software_id, max_versions)
+    }
+
+    fn get_max_versions(&self, software_id: &str) -> Result<usize> {
+        VersionStorage::get_max_versions(self, software_id)
+    }
+
+    fn software_ids(&self) -> Result<Vec<String>> {
+        let _lock = self.lock()?;
+        VersionStorage::software_ids(self)
+    }
+
+    fn dump_software(&self, software_id: &str) -> Result<SoftwareDump> {
+        let _lock = self.lock()?;
+        // The other backend cannot hold encrypted blobs or sealed secrets
+        if self.encryption_status(software_id)?.scope.is_some() {
+            return Err(anyhow!("History of {} is protected by a passphrase and cannot be migrated", software_id));
+        }
+        let index = self.load_index(software_id)?;
+
+        let mut versions = Vec::new();
+        for metadata in index.versions.iter().chain(&index.squashed) {
+            let content = self.read_content(software_id, metadata)?;
+            versions.push((metadata.clone(), content));
+        }
+        versions.sort_by_key(|(metadata, _)| metadata.timestamp);
+
+        Ok(SoftwareDump {
+            software_id: software_id.to_string(),
+            max_versions: index.max_versions,
+            retention: index.retention,
+            secret_policy: index.secret_policy,
+            imported_commits: index.imported_commits,
+            versions,
+        })
+    }
+
+    fn load_dump(&self, dump: &SoftwareDump) -> Result<usize> {
+        let _lock = self.lock()?;
+        let mut index = self.load_index(&dump.software_id)?;
+        let blobs = self.blobs(&dump.software_id)?;
+
+        let existing: HashSet<String> = index.versions
+            .iter()
+            .chain(&index.squashed)
+            .map(|v| v.id.clone())
+            .collect();
+
+        let mut added = 0;
+        for (metadata, content) in &dump.versions {
+            if existing.contains(&metadata.id) {
+                continue;
+            }
+            validate_version_id(&metadata.id)?;
+
+            let metadata = VersionMetadata {
+                software_id: dump.software_id.clone(),
+                checksum: blobs.put(content)?,
+                checksum_algorithm: ChecksumAlgorithm::Sha256,
+                file_name: format!("{}.json", metadata.id),
+                layout: VersionLayout::Blob,
+                ..metadata.clone()
+            };
+            self.write_record(&dump.software_id, &metadata)?;
+
+            match metadata.squashed_into {
+                Some(_) => index.squashed.push(metadata),
+                None => index.versions.push(metadata),
+            }
+            added += 1;
+        }
+
+        index.versions.sort_by_key(|v| v.timestamp);
+        index.squashed.sort_by_key(|v| v.timestamp);
+        index.max_versions = dump.max_versions;
+        index.retention = dump.retention.clone();
+        index.secret_policy = dump.secret_policy;
+        index.imported_commits.extend(dump.imported_commits.iter().cloned());
+        self.save_index(&dump.software_id, &index)?;
+
+        Ok(added)
+    }
+}
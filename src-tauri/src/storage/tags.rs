@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+
+use crate::software::ConfigVersion;
+
+use super::{
+    validate_label, validate_tag, validate_version_id, VersionLayout, VersionMetadata, VersionStorage,
+};
+
+impl VersionStorage {
+    // Attach a tag to a version; the same tag may mark several versions
+    pub fn add_tag(&self, software_id: &str, version_id: &str, tag: &str) -> Result<ConfigVersion> {
+        validate_tag(tag)?;
+        self.update_metadata(software_id, version_id, |metadata| {
+            if !metadata.tags.iter().any(|t| t == tag) {
+                metadata.tags.push(tag.to_string());
+            }
+        })
+    }
+
+    pub fn remove_tag(&self, software_id: &str, version_id: &str, tag: &str) -> Result<ConfigVersion> {
+        self.update_metadata(software_id, version_id, |metadata| {
+            metadata.tags.retain(|t| t != tag);
+        })
+    }
+
+    pub fn add_label(&self, software_id: &str, version_id: &str, label: &str) -> Result<ConfigVersion> {
+        validate_label(label)?;
+        let label = label.trim();
+        self.update_metadata(software_id, version_id, |metadata| {
+            if !metadata.labels.iter().any(|l| l == label) {
+                metadata.labels.push(label.to_string());
+            }
+        })
+    }
+
+    pub fn remove_label(&self, software_id: &str, version_id: &str, label: &str) -> Result<ConfigVersion> {
+        let label = label.trim();
+        self.update_metadata(software_id, version_id, |metadata| {
+            metadata.labels.retain(|l| l != label);
+        })
+    }
+
+    // Pinned versions are skipped by cleanup and cannot be deleted
+    pub fn set_pinned(&self, software_id: &str, version_id: &str, pinned: bool) -> Result<ConfigVersion> {
+        self.update_metadata(software_id, version_id, |metadata| {
+            metadata.pinned = pinned;
+        })
+    }
+
+    // Versions carrying a tag, newest first
+    pub fn get_tagged_versions(&self, software_id: &str, tag: &str) -> Result<Vec<ConfigVersion>> {
+        validate_tag(tag)?;
+        let _lock = self.lock()?;
+        let index = self.load_index(software_id)?;
+
+        index.versions
+            .iter()
+            .rev()
+            .filter(|v| v.tags.iter().any(|t| t == tag))
+            .map(|v| self.read_version(software_id, v))
+            .collect()
+    }
+
+    // The newest version carrying a tag, so `known-good` always means the latest known-good state
+    pub fn get_tagged_version(&self, software_id: &str, tag: &str) -> Result<Option<ConfigVersion>> {
+        validate_tag(tag)?;
+        let _lock = self.lock()?;
+        let index = self.load_index(software_id)?;
+
+        match index.versions.iter().rev().find(|v| v.tags.iter().any(|t| t == tag)) {
+            Some(metadata) => Ok(Some(self.read_version(software_id, metadata)?)),
+            None => Ok(None),
+        }
+    }
+
+    // Every tag used in a software's history, sorted
+    pub fn list_tags(&self, software_id: &str) -> Result<Vec<String>> {
+        let _lock = self.lock()?;
+        let index = self.load_index(software_id)?;
+
+        let tags: BTreeSet<&String> = index.versions.iter().flat_map(|v| &v.tags).collect();
+        Ok(tags.into_iter().cloned().collect())
+    }
+
+    // Apply a change to a version's metadata, keeping its record and the index in step
+    fn update_metadata(
+        &self,
+        software_id: &str,
+        version_id: &str,
+        update: impl FnOnce(&mut VersionMetadata),
+    ) -> Result<ConfigVersion> {
+        validate_version_id(version_id)?;
+        let _lock = self.lock()?;
+        let mut index = self.load_index(software_id)?;
+
+        let metadata = index.versions
+            .iter_mut()
+            .find(|v| v.id == version_id)
+            .context("Version not found")?;
+        update(metadata);
+
+        // Legacy inline records hold their content, so it moves to the blob store before the
+        // record is rewritten. The index goes first so it never points at content that is gone.
+        if metadata.layout == VersionLayout::Inline {
+            self.move_to_blob_store(software_id, metadata)?;
+        }
+        let metadata = metadata.clone();
+        self.save_index(software_id, &index)?;
+        self.write_record(software_id, &metadata)?;
+
+        self.load_version(software_id, version_id)
+    }
+}
@@ -0,0 +1,308 @@
+use crate::software::ConfigFormat;
+
+use super::diff::{child_path, index_path};
+
+// Best-effort key path of the setting each line defines, in the notation used by
+// structural diffs (`editor.fontSize`, `plugins[0]`). Lines that only close a block,
+// blank lines and comments get `None`. Shell configs use the defined name.
+pub fn line_key_paths(format: &ConfigFormat, content: &str) -> Vec<Option<String>> {
+    match format {
+        ConfigFormat::Json => json_paths(content),
+        ConfigFormat::Yaml => yaml_paths(content),
+        ConfigFormat::Toml | ConfigFormat::Ini => section_paths(content, matches!(format, ConfigFormat::Toml)),
+        ConfigFormat::Plain | ConfigFormat::Custom => content
+            .lines()
+            .map(|line| element_name(line).map(str::to_string))
+            .collect(),
+    }
+}
+
+// Whether `path` is `filter` itself or nested below it
+pub fn path_matches(path: &str, filter: &str) -> bool {
+    match path.strip_prefix(filter) {
+        Some(rest) => rest.is_empty() || rest.starts_with('.') || rest.starts_with('['),
+        None => false,
+    }
+}
+
+// Name defined by a shell line: `alias ll=`, `export EDITOR=`, `PATH=`, `name()` or `function name`
+pub(super) fn element_name(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let is_name = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+    for prefix in ["alias ", "export ", "function "] {
+        if let Some(rest) = line.strip_prefix(prefix) {
+            let name = rest.trim_start().split(['=', ' ', '(', '{']).next()?;
+            return is_name(name).then_some(name);
+        }
+    }
+
+    let name = line.split(['=', '(']).next()?;
+    let defines = line[name.len()..].starts_with('=') || line[name.len()..].starts_with("()");
+    (defines && is_name(name)).then_some(name)
+}
+
+enum JsonFrame {
+    // Key of the member currently being read
+    Object(Option<String>),
+    // Index of the current element and whether it has started
+    Array(usize, bool),
+}
+
+fn json_path(stack: &[JsonFrame]) -> String {
+    stack.iter().fold(String::new(), |path, frame| match frame {
+        JsonFrame::Object(Some(key)) => child_path(&path, key),
+        JsonFrame::Object(None) => path,
+        JsonFrame::Array(index, _) => index_path(&path, *index),
+    })
+}
+
+fn json_paths(content: &str) -> Vec<Option<String>> {
+    let mut stack: Vec<JsonFrame> = Vec::new();
+    let mut paths = Vec::new();
+
+    for line in content.lines() {
+        let mut line_path = None;
+        let mut chars = line.chars();
+
+        while let Some(c) = chars.next() {
+            if c.is_whitespace() {
+                continue;
+            }
+
+            // Any value start inside an array begins the current element
+            if let Some(JsonFrame::Array(_, started)) = stack.last_mut() {
+                if !*started && c != ']' && c != ',' {
+                    *started = true;
+                    line_path.get_or_insert_with(|| json_path(&stack));
+                }
+            }
+
+            match c {
+                '"' => {
+                    let mut value = String::new();
+                    let mut escaped = false;
+                    for c in chars.by_ref() {
+                        match (escaped, c) {
+                            (false, '\\') => escaped = true,
+                            (false, '"') => break,
+                            _ => {
+                                escaped = false;
+                                value.push(c);
+                            }
+                        }
+                    }
+                    let is_key = chars.clone().find(|c| !c.is_whitespace()) == Some(':');
+                    if let (true, Some(JsonFrame::Object(key))) = (is_key, stack.last_mut()) {
+                        *key = Some(value);
+                        line_path.get_or_insert_with(|| json_path(&stack));
+                    }
+                }
+                '{' => stack.push(JsonFrame::Object(None)),
+                '[' => stack.push(JsonFrame::Array(0, false)),
+                '}' | ']' => {
+                    stack.pop();
+                }
+                ',' => {
+                    if let Some(JsonFrame::Array(index, started)) = stack.last_mut() {
+                        *index += 1;
+                        *started = false;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        paths.push(line_path);
+    }
+
+    paths
+}
+
+fn yaml_paths(content: &str) -> Vec<Option<String>> {
+    // (indent, path) of each open mapping key or sequence item
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    // Next item index of each sequence, keyed by its indent and parent path
+    let mut counters: Vec<(usize, String, usize)> = Vec::new();
+    let mut paths = Vec::new();
+
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed == "---" {
+            paths.push(None);
+            continue;
+        }
+
+        let mut indent = line.len() - trimmed.len();
+        while stack.last().is_some_and(|(i, _)| *i >= indent) {
+            stack.pop();
+        }
+        let mut path = stack.last().map(|(_, p)| p.clone()).unwrap_or_default();
+        let mut rest = trimmed;
+
+        // Sequence items nest under their parent, possibly followed by an inline mapping
+        while let Some(item) = rest.strip_prefix("- ").or_else(|| (rest == "-").then_some("")) {
+            let index = match counters.iter_mut().find(|(i, p, _)| *i == indent && *p == path) {
+                Some((_, _, next)) => {
+                    *next += 1;
+                    *next
+                }
+                None => {
+                    counters.push((indent, path.clone(), 0));
+                    0
+                }
+            };
+            path = index_path(&path, index);
+            stack.push((indent, path.clone()));
+
+            let inline = item.trim_start();
+            indent += rest.len() - inline.len();
+            rest = inline;
+        }
+
+        if let Some(key) = yaml_key(rest) {
+            path = child_path(&path, &key);
+            stack.push((indent, path.clone()));
+        }
+
+        paths.push((!path.is_empty()).then_some(path));
+    }
+
+    paths
+}
+
+// Key of a `key: value` or `key:` line, unquoting quoted keys
+fn yaml_key(line: &str) -> Option<String> {
+    let (key, _) = line.split_once(':').filter(|(_, rest)| rest.is_empty() || rest.starts_with(' '))?;
+    let key = key.trim();
+    let unquoted = key
+        .strip_prefix('"')
+        .and_then(|k| k.strip_suffix('"'))
+        .or_else(|| key.strip_prefix('\'').and_then(|k| k.strip_suffix('\'')))
+        .unwrap_or(key);
+    (!unquoted.is_empty()).then(|| unquoted.to_string())
+}
+
+// INI and TOML: `[section]` headers prefix the `key = value` lines below them.
+// TOML headers and keys may be dotted; INI sections are a single key.
+fn section_paths(content: &str, dotted: bool) -> Vec<Option<String>> {
+    let split = |name: &str| -> Vec<String> {
+        let parts: Vec<&str> = if dotted { name.split('.').collect() } else { vec![name] };
+        parts.into_iter().map(|p| p.trim().trim_matches('"').to_string()).collect()
+    };
+    let join = |base: &str, keys: Vec<String>| keys.iter().fold(base.to_string(), |p, k| child_path(&p, k));
+
+    let mut section = String::new();
+    let mut array_counts: Vec<(String, usize)> = Vec::new();
+    let mut paths = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            paths.push(None);
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+            let base = join("", split(name));
+            let index = match array_counts.iter_mut().find(|(p, _)| *p == base) {
+                Some((_, count)) => {
+                    *count += 1;
+                    *count
+                }
+                None => {
+                    array_counts.push((base.clone(), 0));
+                    0
+                }
+            };
+            section = index_path(&base, index);
+            paths.push(Some(section.clone()));
+        } else if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = join("", split(name));
+            paths.push(Some(section.clone()));
+        } else if let Some((key, _)) = line.split_once('=') {
+            paths.push(Some(join(&section, split(key.trim()))));
+        } else {
+            paths.push(None);
+        }
+    }
+
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(format: ConfigFormat, content: &str) -> Vec<Option<String>> {
+        line_key_paths(&format, content)
+    }
+
+    fn owned(paths: &[Option<&str>]) -> Vec<Option<String>> {
+        paths.iter().map(|p| p.map(str::to_string)).collect()
+    }
+
+    #[test]
+    fn test_json_lines_map_to_keys() {
+        let content = "{\n  \"editor\": {\n    \"fontSize\": 14,\n    \"rulers\": [\n      80,\n      120\n    ]\n  },\n  \"files.exclude\": {}\n}";
+        assert_eq!(
+            paths(ConfigFormat::Json, content),
+            owned(&[
+                None,
+                Some("editor"),
+                Some("editor.fontSize"),
+                Some("editor.rulers"),
+                Some("editor.rulers[0]"),
+                Some("editor.rulers[1]"),
+                None,
+                None,
+                Some("[\"files.exclude\"]"),
+                None,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_yaml_and_toml_lines_map_to_keys() {
+        let yaml = "editor:\n  font: mono\n  plugins:\n    - name: a\n      on: true\n    - b\n# done\ntheme: dark";
+        assert_eq!(
+            paths(ConfigFormat::Yaml, yaml),
+            owned(&[
+                Some("editor"),
+                Some("editor.font"),
+                Some("editor.plugins"),
+                Some("editor.plugins[0].name"),
+                Some("editor.plugins[0].on"),
+                Some("editor.plugins[1]"),
+                None,
+                Some("theme"),
+            ])
+        );
+
+        let toml = "title = \"x\"\n[server.http]\nport = 80\n[[rules]]\nname = \"a\"\n[[rules]]\nname = \"b\"";
+        assert_eq!(
+            paths(ConfigFormat::Toml, toml),
+            owned(&[
+                Some("title"),
+                Some("server.http"),
+                Some("server.http.port"),
+                Some("rules[0]"),
+                Some("rules[0].name"),
+                Some("rules[1]"),
+                Some("rules[1].name"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_shell_lines_map_to_names() {
+        let content = "# aliases\nalias ll='ls -l'\nexport GIT_SSH_COMMAND=\"ssh -i key\"\necho hi";
+        assert_eq!(
+            paths(ConfigFormat::Plain, content),
+            owned(&[None, Some("ll"), Some("GIT_SSH_COMMAND"), None])
+        );
+        assert!(path_matches("editor.fontSize", "editor"));
+        assert!(path_matches("editor[0]", "editor"));
+        assert!(!path_matches("editorial", "editor"));
+    }
+}
@@ -1,8 +1,12 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
+use std::io;
 
-use crate::software::ConfigVersion;
-use crate::storage::VersionStorage;
+use crate::software::{ConfigManager, ConfigVersion, SoftwareDefinition};
+use crate::storage::{PreferenceStore, SoftwarePreferences, VersionStorage, VersionStore};
+
+// Pseudo version id referring to the config file currently on disk
+pub const CURRENT_VERSION_ID: &str = "current";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VersionManager;
@@ -10,57 +14,360 @@ pub struct VersionManager;
 impl VersionManager {
     // Save a new version
     pub fn save_version(
-        storage: &VersionStorage,
+        storage: &dyn VersionStore,
+        software_id: &str,
+        content: &str,
+        parsed_content: Option<serde_json::Value>,
+        note: Option<String>,
+        is_auto_save: bool,
+    ) -> Result<ConfigVersion> {
+        let mut version = storage.save_version(software_id, content, note, is_auto_save)?;
+        version.parsed_content = parsed_content;
+        Ok(version)
+    }
+    
+    // Save a version under the software's preferences: auto-saves are refused when disabled and
+    // retention follows `backup_count`
+    pub fn save_config_version(
+        storage: &dyn VersionStore,
+        preferences: &dyn PreferenceStore,
         software_id: &str,
         content: &str,
         parsed_content: Option<serde_json::Value>,
         note: Option<String>,
         is_auto_save: bool,
     ) -> Result<ConfigVersion> {
-        storage.save_version(software_id, content, parsed_content, note, is_auto_save)
+        let prefs = Self::check_auto_save(preferences, software_id, is_auto_save)?;
+        Self::sync_retention(storage, &prefs)?;
+        Self::save_version(storage, software_id, content, parsed_content, note, is_auto_save)
+    }
+    
+    // Save an automatic backup. Backups count as auto-saves, so `backup_count` bounds them
+    // whether or not editor auto-save is on.
+    pub fn save_backup(
+        storage: &dyn VersionStore,
+        preferences: &dyn PreferenceStore,
+        software_id: &str,
+        content: &str,
+        note: String,
+    ) -> Result<ConfigVersion> {
+        Self::sync_retention(storage, &preferences.get_preferences(software_id)?)?;
+        Self::save_version(storage, software_id, content, None, Some(note), true)
+    }
+    
+    // Fail when an auto-save is attempted while the software has auto-save turned off
+    pub fn check_auto_save(
+        preferences: &dyn PreferenceStore,
+        software_id: &str,
+        is_auto_save: bool,
+    ) -> Result<SoftwarePreferences> {
+        let prefs = preferences.get_preferences(software_id)?;
+        if is_auto_save && !prefs.auto_save {
+            return Err(anyhow!("Auto-save is disabled for {}", software_id));
+        }
+        Ok(prefs)
+    }
+    
+    // Save preferences and apply the ones that govern the stored history
+    pub fn save_preferences(
+        storage: &dyn VersionStore,
+        preferences: &dyn PreferenceStore,
+        prefs: SoftwarePreferences,
+    ) -> Result<()> {
+        if prefs.backup_count == 0 {
+            return Err(anyhow!("Backup count must be at least 1"));
+        }
+
+        preferences.save_preferences(prefs.clone())?;
+        Self::sync_retention(storage, &prefs)
+    }
+    
+    // Bring the history's version cap in line with `backup_count`, which is the setting of record
+    pub fn sync_retention(storage: &dyn VersionStore, prefs: &SoftwarePreferences) -> Result<()> {
+        if storage.get_max_versions(&prefs.software_id)? != prefs.backup_count {
+            storage.set_max_versions(&prefs.software_id, prefs.backup_count)?;
+        }
+        Ok(())
+    }
+    
+    // Apply every software's effective `backup_count`, after the inherited defaults change
+    pub fn sync_all_retention(storage: &dyn VersionStore, preferences: &dyn PreferenceStore) -> Result<()> {
+        for software_id in storage.software_ids()? {
+            Self::sync_retention(storage, &preferences.get_preferences(&software_id)?)?;
+        }
+        Ok(())
+    }
+    
+    // Reconcile `backup_count` with the version cap kept by older releases in each index. The
+    // larger of the two wins so that no history is pruned by the upgrade. Returns the software
+    // whose settings changed.
+    pub fn reconcile_preferences(
+        storage: &dyn VersionStore,
+        preferences: &dyn PreferenceStore,
+    ) -> Result<Vec<String>> {
+        let mut reconciled = Vec::new();
+        for software_id in storage.software_ids()? {
+            let mut prefs = preferences.get_preferences(&software_id)?;
+            let max_versions = storage.get_max_versions(&software_id)?;
+            if max_versions == prefs.backup_count {
+                continue;
+            }
+
+            let backup_count = prefs.backup_count.max(max_versions);
+            // Inherited preferences stay inherited unless the count has to change
+            if backup_count != prefs.backup_count {
+                prefs.backup_count = backup_count;
+                preferences.save_preferences(prefs.clone())?;
+            }
+            Self::sync_retention(storage, &prefs)?;
+            reconciled.push(software_id);
+        }
+
+        Ok(reconciled)
     }
     
     // Get version history for a software
     pub fn get_history(
-        storage: &VersionStorage,
+        storage: &dyn VersionStore,
         software_id: &str,
         limit: Option<usize>,
     ) -> Result<Vec<ConfigVersion>> {
-        storage.get_history(software_id, limit)
+        Ok(storage
+            .get_history(software_id, limit)?
+            .into_iter()
+            .map(Self::with_parsed_content)
+            .collect())
     }
     
     // Get a specific version
     pub fn get_version(
+        storage: &dyn VersionStore,
+        software_id: &str,
+        version_id: &str,
+    ) -> Result<ConfigVersion> {
+        storage
+            .get_version(software_id, version_id)
+            .map(Self::with_parsed_content)
+    }
+    
+    // Get a version with its encrypted secrets put back, for writing to the config file
+    pub fn get_restorable_version(
         storage: &VersionStorage,
         software_id: &str,
         version_id: &str,
     ) -> Result<ConfigVersion> {
-        storage.get_version(software_id, version_id)
+        let version = storage.get_version(software_id, version_id)?;
+        Ok(Self::with_parsed_content(storage.reveal_secrets(version)?))
+    }
+    
+    // Get the most recently saved version
+    pub fn get_latest_version(
+        storage: &dyn VersionStore,
+        software_id: &str,
+    ) -> Result<Option<ConfigVersion>> {
+        Ok(storage
+            .get_latest_version(software_id)?
+            .map(Self::with_parsed_content))
+    }
+    
+    // Get the version saved immediately before another one
+    pub fn get_previous_version(
+        storage: &dyn VersionStore,
+        software_id: &str,
+        version_id: &str,
+    ) -> Result<Option<ConfigVersion>> {
+        Ok(storage
+            .get_previous_version(software_id, version_id)?
+            .map(Self::with_parsed_content))
+    }
+    
+    // Get versions carrying a tag, newest first
+    pub fn get_tagged_versions(
+        storage: &dyn VersionStore,
+        software_id: &str,
+        tag: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<ConfigVersion>> {
+        let versions = storage.get_tagged_versions(software_id, tag)?;
+        Ok(versions
+            .into_iter()
+            .take(limit.unwrap_or(usize::MAX))
+            .map(Self::with_parsed_content)
+            .collect())
+    }
+    
+    // Get the newest version carrying a tag
+    pub fn get_tagged_version(
+        storage: &dyn VersionStore,
+        software_id: &str,
+        tag: &str,
+    ) -> Result<Option<ConfigVersion>> {
+        Ok(storage
+            .get_tagged_version(software_id, tag)?
+            .map(Self::with_parsed_content))
+    }
+    
+    // Get a stored version, or the file on disk when `version_id` is CURRENT_VERSION_ID
+    pub fn get_snapshot(
+        storage: &dyn VersionStore,
+        software: &SoftwareDefinition,
+        version_id: &str,
+    ) -> Result<ConfigVersion> {
+        if version_id != CURRENT_VERSION_ID {
+            return Self::get_version(storage, &software.id, version_id);
+        }
+
+        // A missing file compares as empty content
+        let (content, parsed_content) = match ConfigManager::read_config(software) {
+            Ok((content, parsed)) => (content, Some(parsed)),
+            Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::NotFound) => {
+                (String::new(), None)
+            }
+            Err(e) => return Err(e),
+        };
+
+        Ok(ConfigVersion {
+            id: CURRENT_VERSION_ID.to_string(),
+            software_id: software.id.clone(),
+            checksum: Some(VersionStorage::calculate_checksum(&content)),
+            content,
+            parsed_content,
+            timestamp: chrono::Utc::now(),
+            note: None,
+            is_auto_save: false,
+            tags: Vec::new(),
+            labels: Vec::new(),
+            pinned: false,
+            secrets: Vec::new(),
+        })
+    }
+    
+    // Like `get_snapshot`, with the file on disk in the form it would be saved in. Stored versions
+    // hold secrets as placeholders, so this is what they are compared against.
+    pub fn get_comparable_snapshot(
+        storage: &VersionStorage,
+        software: &SoftwareDefinition,
+        version_id: &str,
+    ) -> Result<ConfigVersion> {
+        let snapshot = Self::get_snapshot(storage, software, version_id)?;
+        if version_id != CURRENT_VERSION_ID {
+            return Ok(snapshot);
+        }
+
+        let content = storage.protected_content(&software.id, &snapshot.content)?;
+        if content == snapshot.content {
+            return Ok(snapshot);
+        }
+        Ok(ConfigVersion {
+            checksum: Some(VersionStorage::calculate_checksum(&content)),
+            parsed_content: ConfigManager::parse_content(software, &content).ok(),
+            content,
+            ..snapshot
+        })
     }
     
     // Delete a version
     pub fn delete_version(
-        storage: &VersionStorage,
+        storage: &dyn VersionStore,
         software_id: &str,
         version_id: &str,
     ) -> Result<()> {
         storage.delete_version(software_id, version_id)
     }
     
-    // Set maximum versions to keep
+    // Set maximum versions to keep; stored as the software's `backup_count`
     pub fn set_max_versions(
-        storage: &VersionStorage,
+        storage: &dyn VersionStore,
+        preferences: &dyn PreferenceStore,
         software_id: &str,
         max_versions: usize,
     ) -> Result<()> {
-        storage.set_max_versions(software_id, max_versions)
+        let prefs = preferences.get_preferences(software_id)?;
+        Self::save_preferences(storage, preferences, SoftwarePreferences {
+            backup_count: max_versions,
+            ..prefs
+        })
     }
     
     // Get maximum versions setting
     pub fn get_max_versions(
-        storage: &VersionStorage,
+        preferences: &dyn PreferenceStore,
         software_id: &str,
     ) -> Result<usize> {
-        storage.get_max_versions(software_id)
+        Ok(preferences.get_preferences(software_id)?.backup_count)
+    }
+    
+    // Re-derive parsed content from the raw content using the software's format
+    fn with_parsed_content(mut version: ConfigVersion) -> ConfigVersion {
+        if version.parsed_content.is_some() {
+            return version;
+        }
+
+        let definitions = crate::commands::software::get_software_definitions();
+        if let Some(software) = definitions.iter().find(|d| d.id == version.software_id) {
+            version.parsed_content = ConfigManager::parse_content(software, &version.content).ok();
+        }
+
+        version
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::PreferencesStorage;
+
+    #[test]
+    fn test_preferences_drive_retention_and_auto_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = VersionStorage::with_base_path(dir.path().join("versions")).unwrap();
+        let preferences = PreferencesStorage::with_file_path(dir.path().join("preferences.json"));
+
+        // A cap set on the index by an older release carries over to the preferences
+        storage.set_max_versions("zsh", 3).unwrap();
+        storage.save_version("zsh", "a\n", None, false).unwrap();
+        storage.set_max_versions("git", 7).unwrap();
+        storage.save_version("git", "a\n", None, false).unwrap();
+        preferences
+            .save_preferences(SoftwarePreferences {
+                software_id: "git".to_string(),
+                backup_count: 5,
+                ..Default::default()
+            })
+            .unwrap();
+
+        // A count lowered before the upgrade does not prune history kept under the old cap
+        for content in ["a\n", "b\n", "c\n"] {
+            storage.save_version("npm", content, None, true).unwrap();
+        }
+        preferences
+            .save_preferences(SoftwarePreferences {
+                software_id: "npm".to_string(),
+                backup_count: 2,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let reconciled = VersionManager::reconcile_preferences(&storage, &preferences).unwrap();
+        assert_eq!(reconciled, vec!["git".to_string(), "npm".to_string(), "zsh".to_string()]);
+        assert_eq!(VersionManager::get_max_versions(&preferences, "zsh").unwrap(), 20);
+        assert_eq!(VersionManager::get_max_versions(&preferences, "git").unwrap(), 7);
+        assert_eq!(VersionManager::get_max_versions(&preferences, "npm").unwrap(), 20);
+        assert_eq!(storage.get_history("npm", None).unwrap().len(), 3);
+        assert!(VersionManager::reconcile_preferences(&storage, &preferences).unwrap().is_empty());
+
+        VersionManager::set_max_versions(&storage, &preferences, "zsh", 2).unwrap();
+        assert_eq!(storage.get_max_versions("zsh").unwrap(), 2);
+        for content in ["b\n", "c\n", "d\n"] {
+            VersionManager::save_config_version(&storage, &preferences, "zsh", content, None, None, true).unwrap();
+        }
+        let auto_saves = storage.get_history("zsh", None).unwrap().into_iter().filter(|v| v.is_auto_save).count();
+        assert_eq!(auto_saves, 2);
+
+        let prefs = preferences.get_preferences("zsh").unwrap();
+        VersionManager::save_preferences(&storage, &preferences, SoftwarePreferences { auto_save: false, ..prefs })
+            .unwrap();
+        assert!(VersionManager::save_config_version(&storage, &preferences, "zsh", "e\n", None, None, true).is_err());
+        assert!(VersionManager::save_config_version(&storage, &preferences, "zsh", "e\n", None, None, false).is_ok());
     }
-}
\ No newline at end of file
+}
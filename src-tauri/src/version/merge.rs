@@ -0,0 +1,385 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+use crate::software::{ConfigFormat, ConfigManager, ConfigVersion, SoftwareDefinition};
+use crate::storage::VersionStorage;
+
+use super::{VersionManager, CURRENT_VERSION_ID};
+
+// How conflicting hunks or keys are resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    // Report conflicts and leave the file untouched
+    #[default]
+    Abort,
+    // Keep the current file's side of each conflict
+    Ours,
+    // Take the restored version's side of each conflict
+    Theirs,
+}
+
+// A region of lines that both sides changed differently
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextConflict {
+    // 1-based line in the base where the conflicting region starts
+    pub base_start: usize,
+    pub base: Vec<String>,
+    pub ours: Vec<String>,
+    pub theirs: Vec<String>,
+}
+
+// A key path whose value both sides changed differently
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValueConflict {
+    pub path: String,
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MergeConflicts {
+    Text { conflicts: Vec<TextConflict> },
+    Structured { conflicts: Vec<ValueConflict> },
+}
+
+impl MergeConflicts {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            MergeConflicts::Text { conflicts } => conflicts.is_empty(),
+            MergeConflicts::Structured { conflicts } => conflicts.is_empty(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeResult {
+    pub base_version_id: Option<String>,
+    pub theirs_version_id: String,
+    pub content: String,
+    pub conflicts: MergeConflicts,
+    // Whether the merged content was written to the config file
+    pub applied: bool,
+    // Version recorded for the merged content, when applied
+    pub new_version_id: Option<String>,
+}
+
+// One side's replacement of the base lines [start, end)
+#[derive(Debug, Clone)]
+struct Edit {
+    start: usize,
+    end: usize,
+    lines: Vec<String>,
+}
+
+pub struct MergeEngine;
+
+impl MergeEngine {
+    // Merge an old version back into the current file. By default the base is the
+    // version saved just before `version_id`, so only the changes that version
+    // introduced are replayed on top of the current file, like a cherry-pick.
+    pub fn restore_merge(
+        storage: &VersionStorage,
+        software: &SoftwareDefinition,
+        version_id: &str,
+        base_version_id: Option<&str>,
+        resolution: ConflictResolution,
+        dry_run: bool,
+    ) -> Result<MergeResult> {
+        let theirs = VersionManager::get_restorable_version(storage, &software.id, version_id)?;
+        let ours = VersionManager::get_snapshot(storage, software, CURRENT_VERSION_ID)?;
+        let base_id = match base_version_id {
+            Some(id) => Some(id.to_string()),
+            None => VersionManager::get_previous_version(storage, &software.id, version_id)?.map(|v| v.id),
+        };
+        let base = match base_id {
+            Some(id) => Some(VersionManager::get_restorable_version(storage, &software.id, &id)?),
+            None => None,
+        };
+
+        let (content, parsed, conflicts) = Self::merge_versions(software, base.as_ref(), &ours, &theirs, resolution)?;
+
+        let mut result = MergeResult {
+            base_version_id: base.map(|b| b.id),
+            theirs_version_id: theirs.id.clone(),
+            content,
+            conflicts,
+            applied: false,
+            new_version_id: None,
+        };
+
+        if dry_run || (resolution == ConflictResolution::Abort && !result.conflicts.is_empty()) {
+            return Ok(result);
+        }
+
+        ConfigManager::write_raw_config(software, &result.content)?;
+        let version = VersionManager::save_version(
+            storage,
+            &software.id,
+            &result.content,
+            parsed,
+            Some(format!("Merged version {} into current", theirs.id)),
+            false,
+        )?;
+
+        result.applied = true;
+        result.new_version_id = Some(version.id);
+        Ok(result)
+    }
+
+    // Merge using parsed values for structured formats, falling back to lines
+    fn merge_versions(
+        software: &SoftwareDefinition,
+        base: Option<&ConfigVersion>,
+        ours: &ConfigVersion,
+        theirs: &ConfigVersion,
+        resolution: ConflictResolution,
+    ) -> Result<(String, Option<Value>, MergeConflicts)> {
+        let structured = !matches!(software.format, ConfigFormat::Plain | ConfigFormat::Custom);
+        let base_value = match base {
+            Some(base) => base.parsed_content.clone(),
+            None => Some(Value::Object(Map::new())),
+        };
+
+        if let (true, Some(base_value), Some(ours_value), Some(theirs_value)) =
+            (structured, base_value, &ours.parsed_content, &theirs.parsed_content)
+        {
+            let (merged, conflicts) = Self::merge_values(&base_value, ours_value, theirs_value, resolution);
+            let content = ConfigManager::serialize_content(software, &merged)
+                .context("Failed to serialize merged configuration")?;
+            return Ok((content, Some(merged), MergeConflicts::Structured { conflicts }));
+        }
+
+        let base_content = base.map(|b| b.content.as_str()).unwrap_or("");
+        let (content, conflicts) = Self::merge_text(base_content, &ours.content, &theirs.content, resolution);
+        let parsed = ConfigManager::parse_content(software, &content).ok();
+        Ok((content, parsed, MergeConflicts::Text { conflicts }))
+    }
+
+    // Line-based three-way merge. Conflicting regions are filled from the side chosen
+    // by `resolution` (ours for Abort, so the merged text is still usable as a preview).
+    pub fn merge_text(
+        base: &str,
+        ours: &str,
+        theirs: &str,
+        resolution: ConflictResolution,
+    ) -> (String, Vec<TextConflict>) {
+        let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+        let our_edits = Self::edits(&base_lines, ours);
+        let their_edits = Self::edits(&base_lines, theirs);
+
+        // Tag edits with their side (false = ours, true = theirs) and order them along the base
+        let mut all: Vec<(bool, &Edit)> = our_edits.iter().map(|e| (false, e))
+            .chain(their_edits.iter().map(|e| (true, e)))
+            .collect();
+        all.sort_by_key(|(_, e)| (e.start, e.end));
+
+        let mut output = String::new();
+        let mut conflicts = Vec::new();
+        let mut position = 0;
+        let mut i = 0;
+
+        while i < all.len() {
+            // Grow a cluster of overlapping edits
+            let (mut cluster_start, mut cluster_end) = (all[i].1.start, all[i].1.end);
+            let mut j = i + 1;
+            while j < all.len() && Self::overlaps(cluster_start, cluster_end, all[j].1.start, all[j].1.end) {
+                cluster_start = cluster_start.min(all[j].1.start);
+                cluster_end = cluster_end.max(all[j].1.end);
+                j += 1;
+            }
+            let cluster = &all[i..j];
+
+            output.extend(base_lines[position..cluster_start].iter().copied());
+
+            let ours_side: Vec<&Edit> = cluster.iter().filter(|(t, _)| !t).map(|(_, e)| *e).collect();
+            let theirs_side: Vec<&Edit> = cluster.iter().filter(|(t, _)| *t).map(|(_, e)| *e).collect();
+            let our_text = Self::apply(&base_lines, cluster_start, cluster_end, &ours_side);
+            let their_text = Self::apply(&base_lines, cluster_start, cluster_end, &theirs_side);
+
+            if ours_side.is_empty() || our_text == their_text {
+                output.extend(their_text.iter().map(String::as_str));
+            } else if theirs_side.is_empty() {
+                output.extend(our_text.iter().map(String::as_str));
+            } else {
+                conflicts.push(TextConflict {
+                    base_start: cluster_start + 1,
+                    base: base_lines[cluster_start..cluster_end].iter().map(|l| trim_eol(l)).collect(),
+                    ours: our_text.iter().map(|l| trim_eol(l)).collect(),
+                    theirs: their_text.iter().map(|l| trim_eol(l)).collect(),
+                });
+                let chosen = if resolution == ConflictResolution::Theirs { &their_text } else { &our_text };
+                output.extend(chosen.iter().map(String::as_str));
+            }
+
+            position = cluster_end;
+            i = j;
+        }
+
+        output.extend(base_lines[position..].iter().copied());
+        (output, conflicts)
+    }
+
+    // Edits that turn the base lines into `other`
+    fn edits(base_lines: &[&str], other: &str) -> Vec<Edit> {
+        let other_lines: Vec<&str> = other.split_inclusive('\n').collect();
+        capture_diff_slices(Algorithm::Myers, base_lines, &other_lines)
+            .into_iter()
+            .filter(|op| !matches!(op, DiffOp::Equal { .. }))
+            .map(|op| Edit {
+                start: op.old_range().start,
+                end: op.old_range().end,
+                lines: other_lines[op.new_range()].iter().map(|l| l.to_string()).collect(),
+            })
+            .collect()
+    }
+
+    // Edits interact when they replace shared base lines or both insert at the same point.
+    // An insertion right before a replaced region is applied ahead of it.
+    fn overlaps(start_a: usize, end_a: usize, start_b: usize, end_b: usize) -> bool {
+        let both_insert_here = start_a == end_a && start_b == end_b && start_a == start_b;
+        (start_a < end_b && start_b < end_a) || both_insert_here
+    }
+
+    // Apply one side's edits to the base lines [start, end)
+    fn apply(base_lines: &[&str], start: usize, end: usize, edits: &[&Edit]) -> Vec<String> {
+        let mut result = Vec::new();
+        let mut position = start;
+        for edit in edits {
+            result.extend(base_lines[position..edit.start].iter().map(|l| l.to_string()));
+            result.extend(edit.lines.iter().cloned());
+            position = edit.end;
+        }
+        result.extend(base_lines[position..end].iter().map(|l| l.to_string()));
+        result
+    }
+
+    // Key-wise three-way merge of parsed values. Arrays and scalars merge as whole values.
+    pub fn merge_values(
+        base: &Value,
+        ours: &Value,
+        theirs: &Value,
+        resolution: ConflictResolution,
+    ) -> (Value, Vec<ValueConflict>) {
+        let mut conflicts = Vec::new();
+        let merged = Self::merge_value("", Some(base), Some(ours), Some(theirs), resolution, &mut conflicts);
+        (merged.unwrap_or(Value::Null), conflicts)
+    }
+
+    fn merge_value(
+        path: &str,
+        base: Option<&Value>,
+        ours: Option<&Value>,
+        theirs: Option<&Value>,
+        resolution: ConflictResolution,
+        conflicts: &mut Vec<ValueConflict>,
+    ) -> Option<Value> {
+        if ours == theirs || theirs == base {
+            return ours.cloned();
+        }
+        if ours == base {
+            return theirs.cloned();
+        }
+
+        if let (Some(Value::Object(o)), Some(Value::Object(t))) = (ours, theirs) {
+            let empty = Map::new();
+            let b = match base {
+                Some(Value::Object(b)) => b,
+                _ => &empty,
+            };
+
+            let mut merged = Map::new();
+            let keys = o.keys().chain(t.keys().filter(|k| !o.contains_key(*k)));
+            for key in keys {
+                let child = super::diff::child_path(path, key);
+                if let Some(value) = Self::merge_value(&child, b.get(key), o.get(key), t.get(key), resolution, conflicts) {
+                    merged.insert(key.clone(), value);
+                }
+            }
+            return Some(Value::Object(merged));
+        }
+
+        conflicts.push(ValueConflict {
+            path: path.to_string(),
+            base: base.cloned(),
+            ours: ours.cloned(),
+            theirs: theirs.cloned(),
+        });
+
+        if resolution == ConflictResolution::Theirs {
+            theirs.cloned()
+        } else {
+            ours.cloned()
+        }
+    }
+}
+
+fn trim_eol(line: &str) -> String {
+    line.trim_end_matches(['\r', '\n']).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_non_overlapping_changes_merge_cleanly() {
+        let base = "alias ll='ls -l'\nexport EDITOR=vim\nexport PAGER=less\n";
+        let ours = "alias ll='ls -l'\nexport EDITOR=nvim\nexport PAGER=less\n";
+        let theirs = "alias ll='ls -l'\nalias gs='git status'\nexport EDITOR=vim\nexport PAGER=less\n";
+
+        let (merged, conflicts) = MergeEngine::merge_text(base, ours, theirs, ConflictResolution::Abort);
+        assert!(conflicts.is_empty());
+        assert_eq!(merged, "alias ll='ls -l'\nalias gs='git status'\nexport EDITOR=nvim\nexport PAGER=less\n");
+    }
+
+    #[test]
+    fn test_conflicting_changes_are_reported() {
+        let base = "a\nb\nc\n";
+        let ours = "a\nB1\nc\n";
+        let theirs = "a\nB2\nc\n";
+
+        let (merged, conflicts) = MergeEngine::merge_text(base, ours, theirs, ConflictResolution::Abort);
+        assert_eq!(merged, ours);
+        assert_eq!(
+            conflicts,
+            vec![TextConflict {
+                base_start: 2,
+                base: vec!["b".into()],
+                ours: vec!["B1".into()],
+                theirs: vec!["B2".into()],
+            }]
+        );
+
+        let (merged, _) = MergeEngine::merge_text(base, ours, theirs, ConflictResolution::Theirs);
+        assert_eq!(merged, theirs);
+    }
+
+    #[test]
+    fn test_structured_merge_combines_keys() {
+        let base = json!({ "editor": { "fontSize": 12, "tabSize": 2 }, "theme": "light" });
+        let ours = json!({ "editor": { "fontSize": 14, "tabSize": 2 }, "theme": "light" });
+        let theirs = json!({ "editor": { "fontSize": 12, "tabSize": 4 }, "theme": "dark" });
+
+        let (merged, conflicts) = MergeEngine::merge_values(&base, &ours, &theirs, ConflictResolution::Abort);
+        assert!(conflicts.is_empty());
+        assert_eq!(merged, json!({ "editor": { "fontSize": 14, "tabSize": 4 }, "theme": "dark" }));
+    }
+
+    #[test]
+    fn test_structured_merge_reports_conflicting_keys() {
+        let base = json!({ "theme": "light" });
+        let ours = json!({ "theme": "solarized" });
+        let theirs = json!({ "theme": "dark", "font": "mono" });
+
+        let (merged, conflicts) = MergeEngine::merge_values(&base, &ours, &theirs, ConflictResolution::Abort);
+        assert_eq!(merged, json!({ "theme": "solarized", "font": "mono" }));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, "theme");
+    }
+}
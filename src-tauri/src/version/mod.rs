@@ -1,3 +1,18 @@
+pub mod blame;
+pub mod diff;
+pub mod drift;
+pub mod keypath;
 pub mod manager;
+pub mod merge;
+pub mod restore;
+pub mod scheduler;
+pub mod search;
 
-pub use manager::*;
\ No newline at end of file
+pub use blame::*;
+pub use diff::*;
+pub use drift::*;
+pub use manager::*;
+pub use merge::*;
+pub use restore::*;
+pub use scheduler::*;
+pub use search::*;
@@ -0,0 +1,335 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use crate::software::{SoftwareDefinition, SoftwareDetector};
+use crate::storage::{BackupInterval, BackupSchedule, PreferenceStore, PreferencesStorage, VersionStorage};
+
+use super::{DriftDetector, VersionManager};
+
+// How often the background task checks whether a periodic backup is due
+const TICK: Duration = Duration::from_secs(60);
+
+// What started a backup run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupTrigger {
+    Hourly,
+    Daily,
+    OnStart,
+    BeforeApply,
+    Manual,
+}
+
+impl BackupTrigger {
+    fn note(self) -> &'static str {
+        match self {
+            BackupTrigger::Hourly => "Hourly backup",
+            BackupTrigger::Daily => "Daily backup",
+            BackupTrigger::OnStart => "Backup on start",
+            BackupTrigger::BeforeApply => "Backup before apply",
+            BackupTrigger::Manual => "Scheduled backup run manually",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupStatus {
+    Saved,
+    // Content matches the latest version
+    Unchanged,
+    // `auto_backup` is off for the software
+    Disabled,
+    // No config file on disk
+    Missing,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub software_id: String,
+    pub status: BackupStatus,
+    pub version_id: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupRunReport {
+    pub trigger: BackupTrigger,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub entries: Vec<BackupEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupSchedulerStatus {
+    pub schedule: BackupSchedule,
+    pub next_run: Option<DateTime<Utc>>,
+    pub last_report: Option<BackupRunReport>,
+}
+
+// Takes automatic backups of managed configs, in the background and before the app writes them
+#[derive(Default)]
+pub struct BackupScheduler {
+    last_report: Mutex<Option<BackupRunReport>>,
+    // Serializes runs so a periodic backup never races an on-demand one
+    running: Mutex<()>,
+}
+
+impl BackupScheduler {
+    // Spawn the background task: one run on start if enabled, then periodic runs when due
+    pub fn start(app: AppHandle) {
+        tauri::async_runtime::spawn(async move {
+            let on_start = app
+                .try_state::<PreferencesStorage>()
+                .and_then(|preferences| preferences.get_backup_schedule().ok())
+                .is_some_and(|schedule| schedule.on_start);
+            if on_start {
+                Self::run_in_background(&app, BackupTrigger::OnStart).await;
+            }
+
+            let mut ticker = tokio::time::interval(TICK);
+            loop {
+                ticker.tick().await;
+                let Some(preferences) = app.try_state::<PreferencesStorage>() else {
+                    continue;
+                };
+                let schedule = match preferences.get_backup_schedule() {
+                    Ok(schedule) => schedule,
+                    Err(e) => {
+                        log::warn!("Failed to load backup schedule: {}", e);
+                        continue;
+                    }
+                };
+
+                let (Some(interval), Some(next_run)) = (schedule.interval, schedule.next_run()) else {
+                    continue;
+                };
+                if next_run > Utc::now() {
+                    continue;
+                }
+
+                let trigger = match interval {
+                    BackupInterval::Hourly => BackupTrigger::Hourly,
+                    BackupInterval::Daily => BackupTrigger::Daily,
+                };
+                Self::run_in_background(&app, trigger).await;
+
+                let updated = BackupSchedule {
+                    last_run: Some(Utc::now()),
+                    ..schedule
+                };
+                if let Err(e) = preferences.save_backup_schedule(updated) {
+                    log::warn!("Failed to record backup run: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn run_in_background(app: &AppHandle, trigger: BackupTrigger) {
+        let app = app.clone();
+        let result = tauri::async_runtime::spawn_blocking(move || Self::run_for_app(&app, trigger)).await;
+
+        match result {
+            Ok(Ok(report)) => {
+                let saved = report.entries.iter().filter(|e| e.status == BackupStatus::Saved).count();
+                log::info!("{} saved {} of {} configs", trigger.note(), saved, report.entries.len());
+            }
+            Ok(Err(e)) => log::error!("{} failed: {}", trigger.note(), e),
+            Err(e) => log::error!("{} did not finish: {}", trigger.note(), e),
+        }
+    }
+
+    // Back up every installed software using the storages managed by the app
+    pub fn run_for_app(app: &AppHandle, trigger: BackupTrigger) -> Result<BackupRunReport> {
+        let storage = app
+            .try_state::<VersionStorage>()
+            .ok_or_else(|| anyhow!("Version storage is not available"))?;
+        let preferences = app
+            .try_state::<PreferencesStorage>()
+            .ok_or_else(|| anyhow!("Preferences storage is not available"))?;
+        let scheduler = app
+            .try_state::<BackupScheduler>()
+            .ok_or_else(|| anyhow!("Backup scheduler is not available"))?;
+
+        let definitions: Vec<SoftwareDefinition> = crate::commands::software::get_software_definitions()
+            .into_iter()
+            .filter(SoftwareDetector::is_installed)
+            .collect();
+
+        scheduler.run(&storage, &*preferences, &definitions, trigger)
+    }
+
+    // Back up the given software and keep the report as the latest status
+    pub fn run(
+        &self,
+        storage: &VersionStorage,
+        preferences: &dyn PreferenceStore,
+        definitions: &[SoftwareDefinition],
+        trigger: BackupTrigger,
+    ) -> Result<BackupRunReport> {
+        let _running = self.running.lock().map_err(|_| anyhow!("Backup scheduler is poisoned"))?;
+        let started_at = Utc::now();
+
+        let mut entries = Vec::new();
+        for software in definitions {
+            entries.push(Self::backup_software(storage, preferences, software, trigger));
+        }
+
+        let report = BackupRunReport {
+            trigger,
+            started_at,
+            finished_at: Utc::now(),
+            entries,
+        };
+        if let Ok(mut last_report) = self.last_report.lock() {
+            *last_report = Some(report.clone());
+        }
+
+        Ok(report)
+    }
+
+    // Snapshot a file the app is about to overwrite, if the schedule asks for it
+    pub fn before_apply(
+        &self,
+        storage: &VersionStorage,
+        preferences: &PreferencesStorage,
+        software: &SoftwareDefinition,
+    ) -> Result<Option<BackupEntry>> {
+        if !preferences.get_backup_schedule()?.before_apply {
+            return Ok(None);
+        }
+
+        let _running = self.running.lock().map_err(|_| anyhow!("Backup scheduler is poisoned"))?;
+        let entry = Self::backup_software(storage, preferences, software, BackupTrigger::BeforeApply);
+        match entry.status {
+            // Refuse to overwrite a file whose current content could not be kept
+            BackupStatus::Failed => Err(anyhow!(
+                "Backup before apply failed: {}",
+                entry.error.unwrap_or_default()
+            )),
+            _ => Ok(Some(entry)),
+        }
+    }
+
+    pub fn status(&self, preferences: &PreferencesStorage) -> Result<BackupSchedulerStatus> {
+        let schedule = preferences.get_backup_schedule()?;
+        let last_report = self
+            .last_report
+            .lock()
+            .map_err(|_| anyhow!("Backup scheduler is poisoned"))?
+            .clone();
+
+        Ok(BackupSchedulerStatus {
+            next_run: schedule.next_run(),
+            schedule,
+            last_report,
+        })
+    }
+
+    fn backup_software(
+        storage: &VersionStorage,
+        preferences: &dyn PreferenceStore,
+        software: &SoftwareDefinition,
+        trigger: BackupTrigger,
+    ) -> BackupEntry {
+        let entry = |status, version_id, error| BackupEntry {
+            software_id: software.id.clone(),
+            status,
+            version_id,
+            error,
+        };
+
+        match Self::try_backup(storage, preferences, software, trigger) {
+            Ok((status, version_id)) => entry(status, version_id, None),
+            Err(e) => {
+                log::warn!("Automatic backup of {} failed: {}", software.id, e);
+                entry(BackupStatus::Failed, None, Some(e.to_string()))
+            }
+        }
+    }
+
+    fn try_backup(
+        storage: &VersionStorage,
+        preferences: &dyn PreferenceStore,
+        software: &SoftwareDefinition,
+        trigger: BackupTrigger,
+    ) -> Result<(BackupStatus, Option<String>)> {
+        if !preferences.get_preferences(&software.id)?.auto_backup {
+            return Ok((BackupStatus::Disabled, None));
+        }
+
+        let Some(path) = DriftDetector::resolve_path(software) else {
+            return Ok((BackupStatus::Missing, None));
+        };
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok((BackupStatus::Missing, None)),
+            Err(e) => return Err(e.into()),
+        };
+
+        if storage.matches_latest(&software.id, &content)? {
+            return Ok((BackupStatus::Unchanged, None));
+        }
+
+        let version = VersionManager::save_backup(
+            storage,
+            preferences,
+            &software.id,
+            &content,
+            trigger.note().to_string(),
+        )?;
+        Ok((BackupStatus::Saved, Some(version.id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::SoftwarePreferences;
+
+    #[test]
+    fn test_backups_skip_unchanged_and_opted_out_software() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = VersionStorage::with_base_path(dir.path().join("versions")).unwrap();
+        let preferences = PreferencesStorage::with_file_path(dir.path().join("preferences.json"));
+
+        let config = dir.path().join("zshrc");
+        let mut zsh = crate::commands::software::get_software_definitions().remove(0);
+        for platform in ["darwin", "linux", "win32"] {
+            zsh.config_paths.insert(platform.to_string(), vec![config.display().to_string()]);
+        }
+        let scheduler = BackupScheduler::default();
+        let run = |trigger| scheduler.run(&storage, &preferences, &[zsh.clone()], trigger).unwrap().entries;
+
+        assert_eq!(run(BackupTrigger::OnStart)[0].status, BackupStatus::Missing);
+
+        fs::write(&config, "export EDITOR=vim\n").unwrap();
+        let saved = run(BackupTrigger::Hourly);
+        assert_eq!(saved[0].status, BackupStatus::Saved);
+        assert_eq!(run(BackupTrigger::Hourly)[0].status, BackupStatus::Unchanged);
+
+        let version = storage.get_version("zsh", saved[0].version_id.as_ref().unwrap()).unwrap();
+        assert_eq!(version.note.as_deref(), Some("Hourly backup"));
+
+        preferences
+            .save_preferences(SoftwarePreferences {
+                software_id: "zsh".to_string(),
+                auto_backup: false,
+                ..Default::default()
+            })
+            .unwrap();
+        fs::write(&config, "export EDITOR=nvim\n").unwrap();
+        assert_eq!(run(BackupTrigger::Daily)[0].status, BackupStatus::Disabled);
+
+        let status = scheduler.status(&preferences).unwrap();
+        assert_eq!(status.last_report.unwrap().trigger, BackupTrigger::Daily);
+        assert!(status.schedule.before_apply);
+    }
+}
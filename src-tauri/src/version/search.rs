@@ -0,0 +1,396 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::software::{ConfigManager, SoftwareDefinition};
+use crate::storage::{HistoryQuery, VersionStorage, VersionStore};
+
+use super::keypath::{line_key_paths, path_matches};
+
+const DEFAULT_LIMIT: usize = 200;
+const MAX_LIMIT: usize = 2000;
+const SNIPPET_CHARS: usize = 160;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchScope {
+    // Current config files and every stored version
+    #[default]
+    All,
+    Current,
+    History,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchQuery {
+    pub query: String,
+    // Treat `query` as a regular expression instead of a literal substring
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub scope: SearchScope,
+    // Limit the search to these software; all software when empty
+    pub software_ids: Vec<String>,
+    // Date filters apply to stored versions only
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    // Only match lines at or below this key path (or shell name)
+    pub key_path: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl SearchQuery {
+    fn max_hits(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub software_id: String,
+    pub file: String,
+    // `None` for the config file currently on disk
+    pub version_id: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub note: Option<String>,
+    // 1-based line and column of the match
+    pub line: usize,
+    pub column: usize,
+    pub snippet: String,
+    pub key_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub hits: Vec<SearchHit>,
+    // More hits exist beyond `limit`
+    pub truncated: bool,
+}
+
+// A config text split into lines with each line's key path
+struct IndexedDocument {
+    lines: Vec<String>,
+    key_paths: Vec<Option<String>>,
+}
+
+impl IndexedDocument {
+    fn new(software: &SoftwareDefinition, content: &str) -> Self {
+        Self {
+            lines: content.lines().map(str::to_string).collect(),
+            key_paths: line_key_paths(&software.format, content),
+        }
+    }
+}
+
+// In-memory index of every distinct config text, keyed by software and content checksum.
+// Versions sharing content share one entry, and entries are only built once per checksum.
+#[derive(Default)]
+pub struct SearchIndex {
+    documents: Mutex<HashMap<String, HashMap<String, Arc<IndexedDocument>>>>,
+}
+
+enum Matcher {
+    Literal { needle: String, case_sensitive: bool },
+    Pattern(Regex),
+}
+
+impl Matcher {
+    fn new(query: &SearchQuery) -> Result<Self> {
+        if query.query.is_empty() {
+            return Err(anyhow!("Search query is empty"));
+        }
+
+        if query.regex {
+            let regex = RegexBuilder::new(&query.query)
+                .case_insensitive(!query.case_sensitive)
+                .build()
+                .map_err(|e| anyhow!("Invalid regular expression: {}", e))?;
+            return Ok(Matcher::Pattern(regex));
+        }
+
+        let needle = match query.case_sensitive {
+            true => query.query.clone(),
+            false => query.query.to_lowercase(),
+        };
+        Ok(Matcher::Literal { needle, case_sensitive: query.case_sensitive })
+    }
+
+    // Byte range of the first match in a line
+    fn find(&self, line: &str) -> Option<(usize, usize)> {
+        match self {
+            Matcher::Pattern(regex) => regex.find(line).map(|m| (m.start(), m.end())),
+            Matcher::Literal { needle, case_sensitive: true } => {
+                line.find(needle.as_str()).map(|start| (start, start + needle.len()))
+            }
+            Matcher::Literal { needle, case_sensitive: false } => {
+                // Lowercasing can change byte lengths, so map the match back by characters
+                let lower: Vec<(usize, char)> = line
+                    .char_indices()
+                    .flat_map(|(i, c)| c.to_lowercase().map(move |l| (i, l)))
+                    .collect();
+                let haystack: String = lower.iter().map(|(_, c)| *c).collect();
+                let start = haystack.find(needle.as_str())?;
+                let first = haystack[..start].chars().count();
+                let count = needle.chars().count();
+                let begin = lower[first].0;
+                let end = lower.get(first + count).map(|(i, _)| *i).unwrap_or(line.len());
+                Some((begin, end.max(begin)))
+            }
+        }
+    }
+}
+
+impl SearchIndex {
+    // Search current config files and stored versions, newest first within each software
+    pub fn search(
+        &self,
+        storage: &VersionStorage,
+        definitions: &[SoftwareDefinition],
+        query: &SearchQuery,
+    ) -> Result<SearchResults> {
+        let matcher = Matcher::new(query)?;
+        let limit = query.max_hits();
+        let mut hits = Vec::new();
+
+        let selected = definitions
+            .iter()
+            .filter(|d| query.software_ids.is_empty() || query.software_ids.contains(&d.id));
+
+        for software in selected {
+            let file = config_file(software);
+
+            if query.scope != SearchScope::History {
+                if let Ok((content, _)) = ConfigManager::read_config(software) {
+                    let checksum = VersionStorage::calculate_checksum(&content);
+                    let document = self.document(&software.id, &checksum, || {
+                        Ok(IndexedDocument::new(software, &content))
+                    })?;
+                    let target = HitTarget {
+                        software,
+                        file: &file,
+                        version_id: None,
+                        timestamp: None,
+                        note: None,
+                    };
+                    collect_hits(&document, &matcher, query, &target, &mut hits);
+                }
+            }
+
+            // Locked history is left out rather than failing the whole search
+            if query.scope != SearchScope::Current && !storage.is_history_locked(&software.id)? {
+                self.search_history(storage, software, &file, &matcher, query, &mut hits)?;
+            }
+
+            if hits.len() > limit {
+                break;
+            }
+        }
+
+        let truncated = hits.len() > limit;
+        hits.truncate(limit);
+        Ok(SearchResults { hits, truncated })
+    }
+
+    fn search_history(
+        &self,
+        storage: &dyn VersionStore,
+        software: &SoftwareDefinition,
+        file: &str,
+        matcher: &Matcher,
+        query: &SearchQuery,
+        hits: &mut Vec<SearchHit>,
+    ) -> Result<()> {
+        let mut page_query = HistoryQuery {
+            since: query.since,
+            until: query.until,
+            limit: Some(500),
+            ..Default::default()
+        };
+        let unfiltered = query.since.is_none() && query.until.is_none();
+        let mut seen = HashSet::new();
+
+        loop {
+            let page = storage.query_history(&software.id, &page_query)?;
+
+            for version in &page.versions {
+                seen.insert(version.checksum.clone());
+                let document = self.document(&software.id, &version.checksum, || {
+                    let stored = storage.get_version(&software.id, &version.id)?;
+                    Ok(IndexedDocument::new(software, &stored.content))
+                })?;
+
+                let target = HitTarget {
+                    software,
+                    file,
+                    version_id: Some(&version.id),
+                    timestamp: Some(version.timestamp),
+                    note: version.note.as_deref(),
+                };
+                collect_hits(&document, matcher, query, &target, hits);
+                if hits.len() > query.max_hits() {
+                    return Ok(());
+                }
+            }
+
+            match page.next_cursor {
+                Some(cursor) => page_query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+
+        // After a full pass, drop entries for content that no version references anymore
+        if unfiltered {
+            let mut documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(software_documents) = documents.get_mut(&software.id) {
+                software_documents.retain(|checksum, _| seen.contains(checksum));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn document(
+        &self,
+        software_id: &str,
+        checksum: &str,
+        build: impl FnOnce() -> Result<IndexedDocument>,
+    ) -> Result<Arc<IndexedDocument>> {
+        let cached = self.documents
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(software_id)
+            .and_then(|documents| documents.get(checksum).cloned());
+        if let Some(document) = cached {
+            return Ok(document);
+        }
+
+        // Build outside the lock; a concurrent search may build the same entry, which is harmless
+        let document = Arc::new(build()?);
+        self.documents
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(software_id.to_string())
+            .or_default()
+            .insert(checksum.to_string(), document.clone());
+        Ok(document)
+    }
+}
+
+struct HitTarget<'a> {
+    software: &'a SoftwareDefinition,
+    file: &'a str,
+    version_id: Option<&'a str>,
+    timestamp: Option<DateTime<Utc>>,
+    note: Option<&'a str>,
+}
+
+fn collect_hits(
+    document: &IndexedDocument,
+    matcher: &Matcher,
+    query: &SearchQuery,
+    target: &HitTarget,
+    hits: &mut Vec<SearchHit>,
+) {
+    for (i, line) in document.lines.iter().enumerate() {
+        let key_path = document.key_paths.get(i).cloned().flatten();
+        if let Some(ref filter) = query.key_path {
+            if !key_path.as_deref().is_some_and(|p| path_matches(p, filter)) {
+                continue;
+            }
+        }
+
+        let Some((start, end)) = matcher.find(line) else {
+            continue;
+        };
+
+        hits.push(SearchHit {
+            software_id: target.software.id.clone(),
+            file: target.file.to_string(),
+            version_id: target.version_id.map(str::to_string),
+            timestamp: target.timestamp,
+            note: target.note.map(str::to_string),
+            line: i + 1,
+            column: line[..start].chars().count() + 1,
+            snippet: snippet(line, start, end),
+            key_path,
+        });
+    }
+}
+
+// The line around a match, shortened to SNIPPET_CHARS characters
+fn snippet(line: &str, start: usize, end: usize) -> String {
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    if chars.len() <= SNIPPET_CHARS {
+        return line.trim().to_string();
+    }
+
+    let match_start = chars.iter().position(|(i, _)| *i >= start).unwrap_or(0);
+    let match_len = chars.iter().filter(|(i, _)| *i >= start && *i < end).count();
+    let context = SNIPPET_CHARS.saturating_sub(match_len) / 2;
+    let first = match_start.saturating_sub(context);
+    let last = (first + SNIPPET_CHARS).min(chars.len());
+
+    let mut text: String = chars[first..last].iter().map(|(_, c)| c).collect();
+    if first > 0 {
+        text.insert(0, '…');
+    }
+    if last < chars.len() {
+        text.push('…');
+    }
+    text
+}
+
+// The config file a software's content lives in: the first existing path, else the primary one
+fn config_file(software: &SoftwareDefinition) -> String {
+    let paths: Vec<String> = software
+        .get_config_path()
+        .unwrap_or_default()
+        .iter()
+        .map(|p| SoftwareDefinition::expand_path(p))
+        .collect();
+
+    paths
+        .iter()
+        .find(|p| std::path::Path::new(p).exists())
+        .or(paths.first())
+        .cloned()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(needle: &str, case_sensitive: bool) -> Matcher {
+        let query = SearchQuery { query: needle.into(), case_sensitive, ..Default::default() };
+        Matcher::new(&query).unwrap()
+    }
+
+    #[test]
+    fn test_matchers_report_byte_ranges() {
+        let line = "export GIT_SSH_COMMAND=\"ssh -i ~/.ssh/work\"";
+        assert_eq!(literal("git_ssh", false).find(line), Some((7, 14)));
+        assert_eq!(literal("git_ssh", true).find(line), None);
+
+        // Case folding must not shift offsets for non-ASCII text
+        assert_eq!(literal("straße", false).find("# Straße x"), Some((2, 9)));
+
+        let query = SearchQuery { query: r"GIT_\w+=".into(), regex: true, case_sensitive: true, ..Default::default() };
+        assert_eq!(Matcher::new(&query).unwrap().find(line), Some((7, 23)));
+
+        let bad = SearchQuery { query: "(".into(), regex: true, ..Default::default() };
+        assert!(Matcher::new(&bad).is_err());
+    }
+
+    #[test]
+    fn test_snippet_is_trimmed_around_match() {
+        let line = format!("{}NEEDLE{}", "a".repeat(300), "b".repeat(300));
+        let text = snippet(&line, 300, 306);
+        assert!(text.contains("NEEDLE"));
+        assert!(text.starts_with('…') && text.ends_with('…'));
+        assert_eq!(text.chars().count(), SNIPPET_CHARS + 2);
+    }
+}